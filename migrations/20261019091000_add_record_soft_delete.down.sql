@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS records_deleted_at_idx;
+
+ALTER TABLE "records"
+    DROP COLUMN deleted_at,
+    DROP COLUMN deleted_by,
+    DROP COLUMN delete_reason;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE "records"
+    ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN deleted_by VARCHAR(255),
+    ADD COLUMN delete_reason TEXT;
+
+CREATE INDEX records_deleted_at_idx ON "records" (deleted_at) WHERE deleted_at IS NOT NULL;
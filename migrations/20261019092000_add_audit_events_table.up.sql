@@ -0,0 +1,33 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS
+    "audit_events" (
+        id BIGSERIAL PRIMARY KEY NOT NULL,
+        occurred_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+        actor_id UUID,
+        actor_username VARCHAR(255),
+        action VARCHAR(50) NOT NULL,
+        entity_type VARCHAR(50) NOT NULL,
+        entity_id VARCHAR(255) NOT NULL,
+        before JSONB,
+        after JSONB,
+        diff JSONB,
+        ip VARCHAR(64),
+        request_id VARCHAR(255)
+);
+
+CREATE INDEX audit_events_entity_idx ON "audit_events" (entity_type, entity_id, occurred_at);
+CREATE INDEX audit_events_actor_idx ON "audit_events" (actor_id, occurred_at);
+
+CREATE OR REPLACE FUNCTION audit_events_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_no_modify
+    BEFORE UPDATE OR DELETE ON "audit_events"
+    FOR EACH ROW EXECUTE FUNCTION audit_events_append_only();
+
+CREATE TRIGGER audit_events_no_truncate
+    BEFORE TRUNCATE ON "audit_events"
+    FOR EACH STATEMENT EXECUTE FUNCTION audit_events_append_only();
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS "record_ledger";
+DROP FUNCTION IF EXISTS record_ledger_append_only();
@@ -0,0 +1,27 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS
+    "record_ledger" (
+        seq BIGSERIAL PRIMARY KEY NOT NULL,
+        record_id INTEGER NOT NULL,
+        action VARCHAR(50) NOT NULL,
+        payload TEXT NOT NULL,
+        prev_hash CHAR(64) NOT NULL,
+        hash CHAR(64) NOT NULL UNIQUE,
+        created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX record_ledger_record_id_idx ON "record_ledger" (record_id, seq);
+
+CREATE OR REPLACE FUNCTION record_ledger_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'record_ledger is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER record_ledger_no_modify
+    BEFORE UPDATE OR DELETE ON "record_ledger"
+    FOR EACH ROW EXECUTE FUNCTION record_ledger_append_only();
+
+CREATE TRIGGER record_ledger_no_truncate
+    BEFORE TRUNCATE ON "record_ledger"
+    FOR EACH STATEMENT EXECUTE FUNCTION record_ledger_append_only();
@@ -0,0 +1,13 @@
+-- Add down migration script here
+ALTER TABLE "records"
+    DROP COLUMN or_number,
+    DROP COLUMN or_series_id,
+    DROP COLUMN or_scope_key,
+    DROP COLUMN or_year,
+    DROP COLUMN or_sequence;
+
+DROP TABLE IF EXISTS "receipt_counters";
+DROP TABLE IF EXISTS "receipt_series";
+
+ALTER TABLE "users"
+    DROP COLUMN office;
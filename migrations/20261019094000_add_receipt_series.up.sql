@@ -0,0 +1,39 @@
+-- Add up migration script here
+ALTER TABLE "users"
+    ADD COLUMN office VARCHAR(100);
+
+CREATE TABLE IF NOT EXISTS
+    "receipt_series" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        name VARCHAR(100) NOT NULL UNIQUE,
+        prefix VARCHAR(20) NOT NULL,
+        scope VARCHAR(20) NOT NULL DEFAULT 'global' CHECK (scope IN ('global', 'cashier', 'office')),
+        yearly_reset BOOLEAN NOT NULL DEFAULT TRUE,
+        padding INTEGER NOT NULL DEFAULT 6 CHECK (padding BETWEEN 1 AND 12),
+        is_default BOOLEAN NOT NULL DEFAULT FALSE,
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX receipt_series_single_default_idx ON "receipt_series" (is_default) WHERE is_default;
+
+-- One row per numbering stream. The row lock taken when a number is handed out is
+-- held until the record insert commits, so a rolled back insert also rolls back its number.
+CREATE TABLE IF NOT EXISTS
+    "receipt_counters" (
+        series_id INTEGER NOT NULL REFERENCES "receipt_series" (id),
+        scope_key VARCHAR(255) NOT NULL,
+        year INTEGER NOT NULL,
+        last_number BIGINT NOT NULL,
+        PRIMARY KEY (series_id, scope_key, year)
+);
+
+ALTER TABLE "records"
+    ADD COLUMN or_number VARCHAR(100) UNIQUE,
+    ADD COLUMN or_series_id INTEGER REFERENCES "receipt_series" (id),
+    ADD COLUMN or_scope_key VARCHAR(255),
+    ADD COLUMN or_year INTEGER,
+    ADD COLUMN or_sequence BIGINT;
+
+CREATE INDEX records_or_sequence_idx ON "records" (or_series_id, or_scope_key, or_year, or_sequence);
@@ -0,0 +1,12 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS records_finalized_guard ON "records";
+DROP FUNCTION IF EXISTS records_protect_finalized();
+DROP FUNCTION IF EXISTS record_amount(TEXT);
+
+ALTER TABLE "records"
+    DROP COLUMN entry_type,
+    DROP COLUMN reverses_record_id,
+    DROP COLUMN reversal_reason,
+    DROP COLUMN approved_by,
+    DROP COLUMN finalized_at,
+    DROP COLUMN finalized_by;
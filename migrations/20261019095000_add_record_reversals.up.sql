@@ -0,0 +1,49 @@
+-- Add up migration script here
+ALTER TABLE "records"
+    ADD COLUMN entry_type VARCHAR(20) NOT NULL DEFAULT 'payment' CHECK (entry_type IN ('payment', 'void', 'refund')),
+    ADD COLUMN reverses_record_id INTEGER REFERENCES "records" (id),
+    ADD COLUMN reversal_reason TEXT,
+    ADD COLUMN approved_by VARCHAR(255),
+    ADD COLUMN finalized_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN finalized_by VARCHAR(255);
+
+CREATE INDEX records_reverses_record_id_idx ON "records" (reverses_record_id);
+CREATE UNIQUE INDEX records_single_void_idx ON "records" (reverses_record_id) WHERE entry_type = 'void';
+
+-- Amounts are stored as text. NULL for anything that is not a plain decimal so
+-- reports can count those rows instead of failing on them.
+CREATE OR REPLACE FUNCTION record_amount(amount TEXT) RETURNS NUMERIC AS $$
+    SELECT CASE
+        WHEN REPLACE(TRIM(amount), ',', '') ~ '^-?[0-9]+(\.[0-9]{0,2})?$' THEN REPLACE(TRIM(amount), ',', '')::NUMERIC
+    END;
+$$ LANGUAGE SQL IMMUTABLE;
+
+-- Backstop for the API check: once finalized, a record's financial fields can only be
+-- corrected through linked void and refund entries, and the record cannot be removed.
+CREATE OR REPLACE FUNCTION records_protect_finalized() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        IF OLD.finalized_at IS NOT NULL THEN
+            RAISE EXCEPTION 'record % is finalized and cannot be deleted', OLD.id;
+        END IF;
+        RETURN OLD;
+    END IF;
+
+    IF OLD.finalized_at IS NOT NULL AND (
+        NEW.amount IS DISTINCT FROM OLD.amount
+        OR NEW.payment_for IS DISTINCT FROM OLD.payment_for
+        OR NEW.received_by IS DISTINCT FROM OLD.received_by
+        OR NEW.entry_type IS DISTINCT FROM OLD.entry_type
+        OR NEW.reverses_record_id IS DISTINCT FROM OLD.reverses_record_id
+        OR NEW.finalized_at IS DISTINCT FROM OLD.finalized_at
+        OR NEW.deleted_at IS DISTINCT FROM OLD.deleted_at
+    ) THEN
+        RAISE EXCEPTION 'record % is finalized', OLD.id;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER records_finalized_guard
+    BEFORE UPDATE OR DELETE ON "records"
+    FOR EACH ROW EXECUTE FUNCTION records_protect_finalized();
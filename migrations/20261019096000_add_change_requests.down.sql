@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS "change_requests";
+DROP TABLE IF EXISTS "approval_rules";
@@ -0,0 +1,40 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS
+    "approval_rules" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        name VARCHAR(100) NOT NULL UNIQUE,
+        kind VARCHAR(30) NOT NULL CHECK (kind IN ('field_update', 'delete', 'backdated_create')),
+        -- field_update: the record field that needs approval, NULL for any field.
+        field VARCHAR(50),
+        -- backdated_create: entries dated more than this many days ago need approval.
+        threshold_days INTEGER CHECK (threshold_days >= 0),
+        applies_to_role VARCHAR(50) NOT NULL DEFAULT 'non_admin',
+        approver_role VARCHAR(50) NOT NULL DEFAULT 'admin',
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        CHECK (kind <> 'backdated_create' OR threshold_days IS NOT NULL)
+);
+
+CREATE TABLE IF NOT EXISTS
+    "change_requests" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        action VARCHAR(20) NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+        record_id INTEGER REFERENCES "records" (id) ON DELETE SET NULL,
+        -- Version of the record the change was based on; approval fails if it has moved on.
+        base_version INTEGER,
+        payload JSONB NOT NULL,
+        matched_rules JSONB NOT NULL,
+        approver_role VARCHAR(50) NOT NULL,
+        status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'rejected')),
+        requested_by_id UUID NOT NULL,
+        requested_by VARCHAR(255) NOT NULL,
+        requested_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+        decided_by VARCHAR(255),
+        decided_at TIMESTAMP WITH TIME ZONE,
+        decision_note TEXT,
+        result_record_id INTEGER REFERENCES "records" (id) ON DELETE SET NULL
+);
+
+CREATE INDEX change_requests_status_idx ON "change_requests" (status, requested_at);
+CREATE INDEX change_requests_record_idx ON "change_requests" (record_id);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE "records" DROP COLUMN IF EXISTS student_id;
+
+DROP TABLE IF EXISTS "students";
+DROP FUNCTION IF EXISTS student_name_key(TEXT);
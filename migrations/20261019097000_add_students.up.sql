@@ -0,0 +1,54 @@
+-- Add up migration script here
+-- Case, surrounding/double spaces and periods are not significant when comparing names,
+-- so "DELA CRUZ", "Dela  Cruz" and "dela cruz" all share one key.
+CREATE OR REPLACE FUNCTION student_name_key(value TEXT) RETURNS TEXT AS $$
+    SELECT LOWER(REGEXP_REPLACE(BTRIM(REPLACE(COALESCE(value, ''), '.', '')), '\s+', ' ', 'g'))
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE TABLE IF NOT EXISTS
+    "students" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        student_number VARCHAR(50) UNIQUE,
+        first_name VARCHAR(255) NOT NULL,
+        last_name VARCHAR(255) NOT NULL,
+        mi VARCHAR(255) NOT NULL DEFAULT '',
+        course VARCHAR(255) NOT NULL,
+        year_level VARCHAR(255) NOT NULL,
+        status VARCHAR(20) NOT NULL DEFAULT 'active'
+            CHECK (status IN ('active', 'inactive', 'graduated', 'withdrawn')),
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS students_name_key_idx
+    ON "students" (student_name_key(last_name), student_name_key(first_name), student_name_key(mi));
+
+ALTER TABLE "records"
+    ADD COLUMN student_id INTEGER REFERENCES "students"(id) ON DELETE RESTRICT;
+
+CREATE INDEX IF NOT EXISTS records_student_id_idx ON "records" (student_id);
+
+-- One student per distinct normalized name, spelled the way most of their records spell
+-- it, with course and year level from their latest payment. Look-alikes that do not
+-- normalize to the same key stay separate and show up in GET /api/students/review for a
+-- person to merge.
+INSERT INTO "students" (first_name, last_name, mi, course, year_level)
+SELECT first_name, last_name, mi, course, year_level
+FROM (
+    SELECT
+        MODE() WITHIN GROUP (ORDER BY REGEXP_REPLACE(BTRIM(first_name), '\s+', ' ', 'g')) AS first_name,
+        MODE() WITHIN GROUP (ORDER BY REGEXP_REPLACE(BTRIM(last_name), '\s+', ' ', 'g')) AS last_name,
+        MODE() WITHIN GROUP (ORDER BY BTRIM(mi)) AS mi,
+        (ARRAY_AGG(BTRIM(course) ORDER BY (entry_type = 'payment') DESC, created_at DESC, id DESC))[1] AS course,
+        (ARRAY_AGG(BTRIM(year_level) ORDER BY (entry_type = 'payment') DESC, created_at DESC, id DESC))[1] AS year_level
+    FROM "records"
+    GROUP BY student_name_key(last_name), student_name_key(first_name), student_name_key(mi)
+) spellings
+ORDER BY last_name, first_name, mi;
+
+UPDATE "records" r
+SET student_id = s.id
+FROM "students" s
+WHERE student_name_key(r.last_name) = student_name_key(s.last_name)
+  AND student_name_key(r.first_name) = student_name_key(s.first_name)
+  AND student_name_key(r.mi) = student_name_key(s.mi);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS "payment_categories";
+DROP TABLE IF EXISTS "year_levels";
+DROP TABLE IF EXISTS "courses";
@@ -0,0 +1,74 @@
+-- Add up migration script here
+-- Records keep storing the text values; these tables decide which values are allowed.
+-- Matching is case-insensitive, so each catalog is unique on the lowercased value.
+CREATE TABLE IF NOT EXISTS
+    "courses" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        code VARCHAR(255) NOT NULL,
+        name VARCHAR(255) NOT NULL,
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS courses_code_idx ON "courses" (LOWER(code));
+
+CREATE TABLE IF NOT EXISTS
+    "year_levels" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        code VARCHAR(255) NOT NULL,
+        name VARCHAR(255) NOT NULL,
+        sort_order INTEGER NOT NULL DEFAULT 0,
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS year_levels_code_idx ON "year_levels" (LOWER(code));
+
+CREATE TABLE IF NOT EXISTS
+    "payment_categories" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        name VARCHAR(255) NOT NULL,
+        -- Same text format as records.amount; prefilled when a payment omits its amount.
+        default_amount VARCHAR(255),
+        gl_code VARCHAR(50),
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS payment_categories_name_idx ON "payment_categories" (LOWER(name));
+
+-- Seed each catalog with the values already in use, spelled the way most rows spell
+-- them, so existing records and students stay valid.
+INSERT INTO "courses" (code, name)
+SELECT MODE() WITHIN GROUP (ORDER BY value), MODE() WITHIN GROUP (ORDER BY value)
+FROM (
+    SELECT BTRIM(course) AS value FROM "records"
+    UNION ALL
+    SELECT BTRIM(course) FROM "students"
+) used
+WHERE value <> ''
+GROUP BY LOWER(value)
+ORDER BY 1;
+
+INSERT INTO "year_levels" (code, name, sort_order)
+SELECT code, code, (ROW_NUMBER() OVER (ORDER BY code))::INTEGER
+FROM (
+    SELECT MODE() WITHIN GROUP (ORDER BY value) AS code
+    FROM (
+        SELECT BTRIM(year_level) AS value FROM "records"
+        UNION ALL
+        SELECT BTRIM(year_level) FROM "students"
+    ) used
+    WHERE value <> ''
+    GROUP BY LOWER(value)
+) codes;
+
+INSERT INTO "payment_categories" (name)
+SELECT MODE() WITHIN GROUP (ORDER BY BTRIM(payment_for))
+FROM "records"
+WHERE BTRIM(payment_for) <> ''
+GROUP BY LOWER(BTRIM(payment_for))
+ORDER BY 1;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS "assessments";
+DROP TABLE IF EXISTS "fee_schedule_items";
+DROP TABLE IF EXISTS "fee_schedules";
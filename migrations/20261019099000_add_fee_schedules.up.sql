@@ -0,0 +1,39 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS
+    "fee_schedules" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        term VARCHAR(100) NOT NULL,
+        course VARCHAR(255) NOT NULL,
+        year_level VARCHAR(255) NOT NULL,
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        UNIQUE (term, course, year_level)
+);
+
+CREATE TABLE IF NOT EXISTS
+    "fee_schedule_items" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        fee_schedule_id INTEGER NOT NULL REFERENCES "fee_schedules"(id) ON DELETE CASCADE,
+        payment_for VARCHAR(255) NOT NULL,
+        -- Same text format as records.amount.
+        amount VARCHAR(255) NOT NULL,
+        UNIQUE (fee_schedule_id, payment_for)
+);
+
+-- What a student owes for a term, copied from the schedule when it was generated so
+-- later edits to the schedule do not rewrite past bills.
+CREATE TABLE IF NOT EXISTS
+    "assessments" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        student_id INTEGER NOT NULL REFERENCES "students"(id) ON DELETE RESTRICT,
+        term VARCHAR(100) NOT NULL,
+        payment_for VARCHAR(255) NOT NULL,
+        amount VARCHAR(255) NOT NULL,
+        fee_schedule_id INTEGER REFERENCES "fee_schedules"(id) ON DELETE SET NULL,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        created_by VARCHAR(255) NOT NULL,
+        UNIQUE (student_id, term, payment_for)
+);
+
+CREATE INDEX IF NOT EXISTS assessments_fee_schedule_id_idx ON "assessments" (fee_schedule_id);
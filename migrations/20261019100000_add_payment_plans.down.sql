@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS "installment_allocations";
+DROP TABLE IF EXISTS "plan_installments";
+DROP TABLE IF EXISTS "payment_plans";
@@ -0,0 +1,44 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS
+    "payment_plans" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        student_id INTEGER NOT NULL REFERENCES "students"(id) ON DELETE RESTRICT,
+        payment_for VARCHAR(255) NOT NULL,
+        -- Same text format as records.amount.
+        total_amount VARCHAR(255) NOT NULL,
+        -- Payments dated before this day are not applied to the plan.
+        start_date DATE NOT NULL DEFAULT CURRENT_DATE,
+        status VARCHAR(20) NOT NULL DEFAULT 'active'
+            CHECK (status IN ('active', 'completed', 'cancelled')),
+        note TEXT,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        created_by VARCHAR(255) NOT NULL,
+        updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS payment_plans_student_idx ON "payment_plans" (student_id, payment_for);
+
+CREATE TABLE IF NOT EXISTS
+    "plan_installments" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        plan_id INTEGER NOT NULL REFERENCES "payment_plans"(id) ON DELETE CASCADE,
+        sequence INTEGER NOT NULL,
+        due_date DATE NOT NULL,
+        amount VARCHAR(255) NOT NULL,
+        UNIQUE (plan_id, sequence)
+);
+
+CREATE INDEX IF NOT EXISTS plan_installments_due_date_idx ON "plan_installments" (due_date);
+
+-- How much of each payment went to which installment. Derived from the records and
+-- rebuilt whenever a payment for the student and category changes.
+CREATE TABLE IF NOT EXISTS
+    "installment_allocations" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        installment_id INTEGER NOT NULL REFERENCES "plan_installments"(id) ON DELETE CASCADE,
+        record_id INTEGER NOT NULL REFERENCES "records"(id) ON DELETE CASCADE,
+        amount VARCHAR(255) NOT NULL,
+        UNIQUE (installment_id, record_id)
+);
+
+CREATE INDEX IF NOT EXISTS installment_allocations_record_id_idx ON "installment_allocations" (record_id);
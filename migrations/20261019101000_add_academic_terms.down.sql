@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE "records" DROP COLUMN IF EXISTS academic_term_id;
+
+DROP TABLE IF EXISTS "academic_terms";
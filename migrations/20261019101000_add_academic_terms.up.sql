@@ -0,0 +1,24 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS
+    "academic_terms" (
+        id SERIAL PRIMARY KEY NOT NULL,
+        -- e.g. '2026-2027'
+        school_year VARCHAR(9) NOT NULL,
+        semester VARCHAR(20) NOT NULL
+            CHECK (semester IN ('first', 'second', 'summer')),
+        start_date DATE NOT NULL,
+        end_date DATE NOT NULL,
+        is_current BOOLEAN NOT NULL DEFAULT FALSE,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        UNIQUE (school_year, semester),
+        CHECK (end_date >= start_date)
+);
+
+-- At most one term is current.
+CREATE UNIQUE INDEX IF NOT EXISTS academic_terms_current_idx ON "academic_terms" (is_current) WHERE is_current;
+
+-- Inferred from created_at when a record is written, or when a term covering it is added.
+ALTER TABLE "records" ADD COLUMN IF NOT EXISTS academic_term_id INTEGER REFERENCES "academic_terms"(id) ON DELETE RESTRICT;
+
+CREATE INDEX IF NOT EXISTS records_academic_term_id_idx ON "records" (academic_term_id);
@@ -0,0 +1,20 @@
+-- Add up migration script here
+-- One row per generated export file. Only the owner can download it, and the file is
+-- removed once the job expires.
+CREATE TABLE IF NOT EXISTS
+    "export_jobs" (
+        id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+        owner_id UUID NOT NULL REFERENCES "users"(id) ON DELETE CASCADE,
+        kind VARCHAR(50) NOT NULL,
+        -- Name offered to the browser; the file on disk is named after the id.
+        filename VARCHAR(255) NOT NULL,
+        file_path TEXT NOT NULL,
+        status VARCHAR(20) NOT NULL DEFAULT 'ready'
+            CHECK (status IN ('ready', 'expired')),
+        row_count INTEGER NOT NULL DEFAULT 0,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS export_jobs_owner_idx ON "export_jobs" (owner_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS export_jobs_expires_at_idx ON "export_jobs" (expires_at) WHERE status = 'ready';
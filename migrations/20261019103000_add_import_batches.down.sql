@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS records_import_batch_id_idx;
+ALTER TABLE "records" DROP COLUMN IF EXISTS import_batch_id;
+DROP TABLE IF EXISTS "import_batches";
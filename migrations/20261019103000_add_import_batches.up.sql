@@ -0,0 +1,19 @@
+-- Add up migration script here
+-- One row per committed import. Records created by it point back here, so a bank list
+-- can be traced or reviewed as a whole. Dry runs leave nothing behind.
+CREATE TABLE IF NOT EXISTS
+    "import_batches" (
+        id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+        created_by_id UUID NOT NULL REFERENCES "users"(id),
+        created_by VARCHAR(100) NOT NULL,
+        filename VARCHAR(255) NOT NULL,
+        format VARCHAR(10) NOT NULL
+            CHECK (format IN ('csv', 'xlsx')),
+        row_count INTEGER NOT NULL DEFAULT 0,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+ALTER TABLE "records"
+    ADD COLUMN IF NOT EXISTS import_batch_id UUID REFERENCES "import_batches"(id) ON DELETE RESTRICT;
+
+CREATE INDEX IF NOT EXISTS records_import_batch_id_idx ON "records" (import_batch_id) WHERE import_batch_id IS NOT NULL;
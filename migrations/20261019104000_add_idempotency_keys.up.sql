@@ -0,0 +1,18 @@
+-- Add up migration script here
+-- Responses to requests sent with an Idempotency-Key header, so a retried request gets
+-- the original answer instead of doing the work twice. Keys are scoped to the user who
+-- sent them.
+CREATE TABLE IF NOT EXISTS
+    "idempotency_keys" (
+        owner_id UUID NOT NULL REFERENCES "users"(id) ON DELETE CASCADE,
+        key VARCHAR(255) NOT NULL,
+        -- SHA-256 of the method, path and body, hex encoded.
+        request_hash CHAR(64) NOT NULL,
+        status_code INTEGER,
+        response JSONB,
+        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+        expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+        PRIMARY KEY (owner_id, key)
+);
+
+CREATE INDEX IF NOT EXISTS idempotency_keys_expires_at_idx ON "idempotency_keys" (expires_at);
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Duplicate checks look for live payments of the same category close in time.
+CREATE INDEX IF NOT EXISTS records_duplicate_lookup_idx ON "records" (payment_for, created_at)
+    WHERE deleted_at IS NULL AND entry_type = 'payment';
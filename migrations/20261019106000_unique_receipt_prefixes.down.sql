@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS receipt_series_prefix_idx;
+
+ALTER TABLE "receipt_series"
+    DROP CONSTRAINT IF EXISTS receipt_series_prefix_check,
+    DROP COLUMN IF EXISTS legacy_format;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- Numbers of new series put a '-' after the prefix. Series that already exist keep the
+-- format they have been issuing: their prefix is printed as it is, with no '-' added.
+ALTER TABLE "receipt_series"
+    ADD COLUMN legacy_format BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE "receipt_series" SET legacy_format = TRUE;
+
+-- The prefix is the first segment of every new-format number, so it alone has to tell
+-- series apart. Existing prefixes are left as they were, even where they break the rule.
+ALTER TABLE "receipt_series"
+    ADD CONSTRAINT receipt_series_prefix_check CHECK (legacy_format OR prefix ~ '^[A-Za-z0-9]+$');
+
+CREATE UNIQUE INDEX receipt_series_prefix_idx ON "receipt_series" (LOWER(prefix)) WHERE NOT legacy_format;
@@ -0,0 +1,26 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS assessments_academic_term_id_idx;
+
+ALTER TABLE "assessments"
+    DROP CONSTRAINT assessments_student_id_academic_term_payment_for_key,
+    DROP CONSTRAINT assessments_term_check;
+ALTER TABLE "assessments" RENAME COLUMN legacy_term TO term;
+UPDATE "assessments" a SET term = t.school_year || ' ' || t.semester FROM "academic_terms" t WHERE t.id = a.academic_term_id;
+ALTER TABLE "assessments"
+    DROP COLUMN academic_term_id,
+    ALTER COLUMN term SET NOT NULL,
+    ADD CONSTRAINT assessments_student_id_term_payment_for_key UNIQUE (student_id, term, payment_for);
+
+ALTER TABLE "fee_schedules"
+    DROP CONSTRAINT fee_schedules_academic_term_course_year_level_key,
+    DROP CONSTRAINT fee_schedules_term_check;
+ALTER TABLE "fee_schedules" RENAME COLUMN legacy_term TO term;
+UPDATE "fee_schedules" f SET term = t.school_year || ' ' || t.semester FROM "academic_terms" t WHERE t.id = f.academic_term_id;
+ALTER TABLE "fee_schedules"
+    DROP COLUMN academic_term_id,
+    ALTER COLUMN term SET NOT NULL,
+    ADD CONSTRAINT fee_schedules_term_course_year_level_key UNIQUE (term, course, year_level);
+
+ALTER TABLE "academic_terms"
+    DROP CONSTRAINT IF EXISTS academic_terms_no_overlap,
+    DROP COLUMN IF EXISTS legacy_overlap;
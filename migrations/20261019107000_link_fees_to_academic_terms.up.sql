@@ -0,0 +1,99 @@
+-- Add up migration script here
+-- Overlapping terms would leave a payment date in two of them; the API check alone
+-- can race with a concurrent write. Terms that already overlap are flagged and left
+-- out of the constraint until their dates are corrected.
+ALTER TABLE "academic_terms"
+    ADD COLUMN legacy_overlap BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE "academic_terms" t
+SET legacy_overlap = TRUE
+WHERE EXISTS (
+    SELECT 1 FROM "academic_terms" o
+    WHERE o.id <> t.id AND o.start_date <= t.end_date AND o.end_date >= t.start_date
+);
+
+ALTER TABLE "academic_terms"
+    ADD CONSTRAINT academic_terms_no_overlap EXCLUDE USING gist (daterange(start_date, end_date, '[]') WITH &&)
+    WHERE (NOT legacy_overlap);
+
+-- The free-text term is kept as `legacy_term` on rows it could not be linked from, so
+-- they show up in GET /api/academic-terms/unlinked and can be linked once the term exists.
+ALTER TABLE "fee_schedules"
+    ADD COLUMN academic_term_id INTEGER REFERENCES "academic_terms"(id) ON DELETE RESTRICT,
+    DROP CONSTRAINT fee_schedules_term_course_year_level_key,
+    ALTER COLUMN term DROP NOT NULL;
+ALTER TABLE "fee_schedules" RENAME COLUMN term TO legacy_term;
+
+ALTER TABLE "assessments"
+    ADD COLUMN academic_term_id INTEGER REFERENCES "academic_terms"(id) ON DELETE RESTRICT,
+    DROP CONSTRAINT assessments_student_id_term_payment_for_key,
+    ALTER COLUMN term DROP NOT NULL;
+ALTER TABLE "assessments" RENAME COLUMN term TO legacy_term;
+
+-- Free-text terms are matched on their school year and semester, e.g. "2026-2027 1st sem".
+-- A term that matches more than one academic term is left for an admin to link.
+CREATE FUNCTION pg_temp.term_matches(legacy_term TEXT, t "academic_terms") RETURNS BOOLEAN AS $$
+    SELECT POSITION(t.school_year IN legacy_term) > 0
+        AND LOWER(legacy_term) ~ CASE t.semester
+            WHEN 'first' THEN '(first|1st)'
+            WHEN 'second' THEN '(second|2nd)'
+            ELSE 'summer'
+        END
+$$ LANGUAGE SQL STABLE;
+
+UPDATE "fee_schedules" f
+SET academic_term_id = m.academic_term_id
+FROM (
+    SELECT f.id, MIN(t.id) AS academic_term_id
+    FROM "fee_schedules" f
+    JOIN "academic_terms" t ON pg_temp.term_matches(f.legacy_term, t)
+    GROUP BY f.id
+    HAVING COUNT(*) = 1
+) m
+WHERE m.id = f.id;
+
+UPDATE "assessments" a
+SET academic_term_id = f.academic_term_id
+FROM "fee_schedules" f
+WHERE a.fee_schedule_id = f.id AND a.legacy_term = f.legacy_term;
+
+UPDATE "assessments" a
+SET academic_term_id = m.academic_term_id
+FROM (
+    SELECT a.id, MIN(t.id) AS academic_term_id
+    FROM "assessments" a
+    JOIN "academic_terms" t ON pg_temp.term_matches(a.legacy_term, t)
+    WHERE a.academic_term_id IS NULL
+    GROUP BY a.id
+    HAVING COUNT(*) = 1
+) m
+WHERE m.id = a.id;
+
+-- Two spellings of one term, e.g. "1st sem" and "First Semester", can land on the same
+-- key. The oldest row keeps the link and the rest are left unlinked.
+UPDATE "fee_schedules" f
+SET academic_term_id = NULL
+WHERE EXISTS (
+    SELECT 1 FROM "fee_schedules" o
+    WHERE o.academic_term_id = f.academic_term_id AND o.course = f.course AND o.year_level = f.year_level AND o.id < f.id
+);
+
+UPDATE "assessments" a
+SET academic_term_id = NULL
+WHERE EXISTS (
+    SELECT 1 FROM "assessments" o
+    WHERE o.academic_term_id = a.academic_term_id AND o.student_id = a.student_id AND o.payment_for = a.payment_for AND o.id < a.id
+);
+
+UPDATE "fee_schedules" SET legacy_term = NULL WHERE academic_term_id IS NOT NULL;
+UPDATE "assessments" SET legacy_term = NULL WHERE academic_term_id IS NOT NULL;
+
+ALTER TABLE "fee_schedules"
+    ADD CONSTRAINT fee_schedules_term_check CHECK (academic_term_id IS NOT NULL OR legacy_term IS NOT NULL),
+    ADD CONSTRAINT fee_schedules_academic_term_course_year_level_key UNIQUE (academic_term_id, course, year_level);
+
+ALTER TABLE "assessments"
+    ADD CONSTRAINT assessments_term_check CHECK (academic_term_id IS NOT NULL OR legacy_term IS NOT NULL),
+    ADD CONSTRAINT assessments_student_id_academic_term_payment_for_key UNIQUE (student_id, academic_term_id, payment_for);
+
+CREATE INDEX IF NOT EXISTS assessments_academic_term_id_idx ON "assessments" (academic_term_id);
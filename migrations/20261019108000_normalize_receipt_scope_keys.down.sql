@@ -0,0 +1,3 @@
+-- Add down migration script here
+-- The original spelling of merged scope keys is not kept; the normalized counters
+-- keep working with the previous code, which uppercased keys when printing them.
@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- Scope keys are now trimmed and uppercased before they reach a counter, matching how
+-- they are printed. Counters that only differed by case merge into one that continues
+-- after the highest number either of them handed out.
+INSERT INTO "receipt_counters" (series_id, scope_key, year, last_number)
+    SELECT series_id, UPPER(BTRIM(scope_key)), year, MAX(last_number)
+    FROM "receipt_counters"
+    WHERE scope_key <> UPPER(BTRIM(scope_key))
+    GROUP BY series_id, UPPER(BTRIM(scope_key)), year
+ON CONFLICT (series_id, scope_key, year) DO UPDATE
+    SET last_number = GREATEST("receipt_counters".last_number, EXCLUDED.last_number);
+
+DELETE FROM "receipt_counters" WHERE scope_key <> UPPER(BTRIM(scope_key));
+
+UPDATE "records" SET or_scope_key = UPPER(BTRIM(or_scope_key))
+    WHERE or_scope_key <> UPPER(BTRIM(or_scope_key));
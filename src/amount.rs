@@ -0,0 +1,118 @@
+// Record amounts are stored as free text. These helpers work in whole cents so
+// reversals and refund limits can be computed without floating point.
+
+// Accepts "1500", "-1,500.5" and "1500.50"; more than two decimals is rejected
+// rather than rounded. Mirrors the `record_amount` SQL function.
+pub fn parse_cents(amount: &str) -> Option<i64> {
+    let amount = amount.trim().replace(',', "");
+    let (negative, amount) = match amount.strip_prefix('-') {
+        Some(amount) => (true, amount),
+        None => (false, amount.as_str()),
+    };
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty()
+        || fraction.len() > 2
+        || !whole.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let cents = whole
+        .parse::<i64>()
+        .ok()?
+        .checked_mul(100)?
+        .checked_add(format!("{:0<2}", fraction).parse::<i64>().ok()?)?;
+    Some(if negative { -cents } else { cents })
+}
+
+// "-1500.00", the form amounts are stored in.
+pub fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, cents.unsigned_abs() / 100, cents.unsigned_abs() % 100)
+}
+
+// "1,500.00", for printing.
+pub fn format_grouped(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let digits = (cents.unsigned_abs() / 100).to_string();
+    let first = match digits.len() % 3 {
+        0 => 3,
+        first => first,
+    };
+    let mut grouped = digits[..first].to_string();
+    for group in digits.as_bytes()[first..].chunks(3) {
+        grouped.push(',');
+        grouped.push_str(std::str::from_utf8(group).unwrap_or_default());
+    }
+    format!("{}{}.{:02}", sign, grouped, cents.unsigned_abs() % 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_whole_and_decimal_amounts() {
+        assert_eq!(parse_cents("1500"), Some(150_000));
+        assert_eq!(parse_cents("1500.5"), Some(150_050));
+        assert_eq!(parse_cents("1500.50"), Some(150_050));
+        assert_eq!(parse_cents("1500."), Some(150_000));
+        assert_eq!(parse_cents(" 0.05 "), Some(5));
+    }
+
+    #[test]
+    fn parses_negatives_and_thousands_separators() {
+        assert_eq!(parse_cents("-1,500.5"), Some(-150_050));
+        assert_eq!(parse_cents("1,234,567.89"), Some(123_456_789));
+        assert_eq!(parse_cents("-0.01"), Some(-1));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        assert_eq!(parse_cents(".5"), None);
+        assert_eq!(parse_cents("-.5"), None);
+        assert_eq!(parse_cents("1500.505"), None);
+        assert_eq!(parse_cents("--5"), None);
+        assert_eq!(parse_cents("+5"), None);
+        assert_eq!(parse_cents("1e3"), None);
+        assert_eq!(parse_cents("PHP 100"), None);
+        assert_eq!(parse_cents(""), None);
+        assert_eq!(parse_cents("-"), None);
+    }
+
+    #[test]
+    fn rejects_amounts_that_overflow() {
+        assert_eq!(parse_cents("92233720368547758.07"), Some(i64::MAX));
+        assert_eq!(parse_cents("92233720368547758.08"), None);
+        assert_eq!(parse_cents("99999999999999999999"), None);
+    }
+
+    #[test]
+    fn formats_stored_amounts() {
+        assert_eq!(format_cents(150_050), "1500.50");
+        assert_eq!(format_cents(5), "0.05");
+        assert_eq!(format_cents(0), "0.00");
+        assert_eq!(format_cents(-150_000), "-1500.00");
+        assert_eq!(format_cents(-1), "-0.01");
+        assert_eq!(format_cents(i64::MIN), "-92233720368547758.08");
+    }
+
+    #[test]
+    fn formats_grouped_amounts() {
+        assert_eq!(format_grouped(0), "0.00");
+        assert_eq!(format_grouped(99_999), "999.99");
+        assert_eq!(format_grouped(100_000), "1,000.00");
+        assert_eq!(format_grouped(123_456_789), "1,234,567.89");
+        assert_eq!(format_grouped(-150_050), "-1,500.50");
+        assert_eq!(format_grouped(i64::MAX), "92,233,720,368,547,758.07");
+    }
+
+    #[test]
+    fn formatted_amounts_parse_back() {
+        for cents in [0, 1, -1, 150_050, -123_456_789, i64::MAX] {
+            assert_eq!(parse_cents(&format_cents(cents)), Some(cents));
+            assert_eq!(parse_cents(&format_grouped(cents)), Some(cents));
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use std::sync::Arc;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use axum::response::IntoResponse;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use crate::AppState;
+use crate::approval_model::{ApprovalRule, ChangeRequest, ChangeRequestQuery, CreateApprovalRuleSchema, DecisionSchema, UpdateApprovalRuleSchema};
+use crate::approvals::RULE_KINDS;
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::catalogs;
+use crate::handlers::validate_role;
+use crate::model::User;
+use crate::record_handlers::{apply_create, apply_delete, apply_patch, check_version, lock_record};
+use crate::record_model::{CreateRecordSchema, PatchRecordSchema, Record};
+use crate::response::{bad_request, conflict, db_error, forbidden, not_found};
+
+fn validate_rule(kind: &str, field: Option<&str>, threshold_days: Option<i32>) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if !RULE_KINDS.contains(&kind) {
+        return Err(bad_request(&format!("Invalid kind: {}. Expected one of: {}", kind, RULE_KINDS.join(", "))));
+    }
+    if let Some(field) = field {
+        if field != "student_id" && field != "academic_term_id" && !PatchRecordSchema::default().fields().iter().any(|(column, _)| *column == field) {
+            return Err(bad_request(&format!("Unknown record field: {}", field)));
+        }
+    }
+    if kind == "backdated_create" && threshold_days.is_none() {
+        return Err(bad_request("threshold_days is required for backdated_create rules"));
+    }
+    if threshold_days.is_some_and(|days| days < 0) {
+        return Err(bad_request("threshold_days cannot be negative"));
+    }
+    Ok(())
+}
+
+pub async fn get_approval_rules(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let rules = sqlx::query_as!(
+        ApprovalRule,
+        "SELECT * FROM \"approval_rules\" ORDER BY id"
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "rules": rules
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn create_approval_rule(
+    State(data): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Json(body): Json<CreateApprovalRuleSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if body.name.trim().is_empty() {
+        return Err(bad_request("Name is required"));
+    }
+    validate_rule(&body.kind, body.field.as_deref(), body.threshold_days)?;
+    let applies_to_role = body.applies_to_role.unwrap_or_else(|| "non_admin".to_string());
+    validate_role(&applies_to_role)?;
+    let approver_role = body.approver_role.unwrap_or_else(|| "admin".to_string());
+    validate_role(&approver_role)?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let exists = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM \"approval_rules\" WHERE name = $1)",
+        body.name
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .unwrap_or(false);
+    if exists {
+        return Err(conflict("Approval rule with that name already exists"));
+    }
+
+    let rule = sqlx::query_as!(
+        ApprovalRule,
+        "INSERT INTO \"approval_rules\" (name,kind,field,threshold_days,applies_to_role,approver_role) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+        body.name,
+        body.kind,
+        body.field,
+        body.threshold_days,
+        applies_to_role,
+        approver_role
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "create", "approval_rule", &rule.id.to_string(), None, Some(snapshot(&rule)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "rule": rule
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+pub async fn update_approval_rule(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<UpdateApprovalRuleSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if let Some(role) = &body.applies_to_role {
+        validate_role(role)?;
+    }
+    if let Some(role) = &body.approver_role {
+        validate_role(role)?;
+    }
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = sqlx::query_as!(
+        ApprovalRule,
+        "SELECT * FROM \"approval_rules\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No approval rule found with the provided ID"))?;
+
+    validate_rule(
+        &before.kind,
+        body.field.as_deref().or(before.field.as_deref()),
+        body.threshold_days.or(before.threshold_days),
+    )?;
+
+    let rule = sqlx::query_as!(
+        ApprovalRule,
+        "UPDATE \"approval_rules\" SET name = COALESCE($1, name), field = COALESCE($2, field), threshold_days = COALESCE($3, threshold_days), applies_to_role = COALESCE($4, applies_to_role), approver_role = COALESCE($5, approver_role), active = COALESCE($6, active), updated_at = NOW() WHERE id = $7 RETURNING *",
+        body.name,
+        body.field,
+        body.threshold_days,
+        body.applies_to_role,
+        body.approver_role,
+        body.active,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "update", "approval_rule", &id.to_string(), Some(snapshot(&before)), Some(snapshot(&rule)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "rule": rule
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Admins see every request; everyone else only sees the ones they made.
+pub async fn get_change_requests(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Query(params): Query<ChangeRequestQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM \"change_requests\" WHERE TRUE");
+    if user.role != "admin" {
+        query.push(" AND requested_by_id = ").push_bind(user.id);
+    }
+    if let Some(status) = params.status {
+        query.push(" AND status = ").push_bind(status);
+    }
+    if let Some(record_id) = params.record_id {
+        query.push(" AND record_id = ").push_bind(record_id);
+    }
+    query.push(" ORDER BY requested_at DESC, id DESC");
+
+    let change_requests: Vec<ChangeRequest> = query
+        .build_query_as::<ChangeRequest>()
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "change_requests": change_requests
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn get_change_request(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let change_request = sqlx::query_as!(
+        ChangeRequest,
+        "SELECT * FROM \"change_requests\" WHERE id = $1",
+        id
+    )
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .filter(|change_request| user.role == "admin" || change_request.requested_by_id == user.id)
+        .ok_or_else(|| not_found("No change request found with the provided ID"))?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "change_request": change_request
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Locks a pending request and checks that `user` may decide it: they need the role
+// the matching rule asked for, and nobody reviews their own change.
+async fn lock_pending(
+    conn: &mut PgConnection,
+    id: i32,
+    user: &User,
+) -> Result<ChangeRequest, (StatusCode, Json<serde_json::Value>)> {
+    let change_request = sqlx::query_as!(
+        ChangeRequest,
+        "SELECT * FROM \"change_requests\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No change request found with the provided ID"))?;
+
+    if change_request.status != "pending" {
+        return Err(conflict(&format!("Change request has already been {}", change_request.status)));
+    }
+    if user.role != change_request.approver_role {
+        return Err(forbidden(&format!("Only users with the {} role can decide this change request", change_request.approver_role)));
+    }
+    if change_request.requested_by_id == user.id {
+        return Err(forbidden("You cannot decide your own change request"));
+    }
+
+    Ok(change_request)
+}
+
+async fn decide(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    user: &User,
+    before: &ChangeRequest,
+    status: &str,
+    note: Option<&str>,
+    result_record_id: Option<i32>,
+) -> Result<ChangeRequest, (StatusCode, Json<serde_json::Value>)> {
+    let change_request = sqlx::query_as!(
+        ChangeRequest,
+        "UPDATE \"change_requests\" SET status = $1, decided_by = $2, decided_at = NOW(), decision_note = $3, result_record_id = $4 WHERE id = $5 RETURNING *",
+        status,
+        user.username,
+        note,
+        result_record_id,
+        before.id
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    let action = if status == "approved" { "approve" } else { "reject" };
+    log_event(&mut *conn, ctx, action, "change_request", &before.id.to_string(), Some(snapshot(before)), Some(snapshot(&change_request)))
+        .await
+        .map_err(db_error)?;
+
+    Ok(change_request)
+}
+
+fn payload<T: DeserializeOwned>(change_request: &ChangeRequest) -> Result<T, (StatusCode, Json<serde_json::Value>)> {
+    serde_json::from_value(change_request.payload.clone()).map_err(|e| {
+        let error_response = json!({
+            "status": "fail",
+            "message": format!("Invalid change request payload: {}", e),
+        });
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+    })
+}
+
+async fn target_record(
+    conn: &mut PgConnection,
+    change_request: &ChangeRequest,
+) -> Result<Record, (StatusCode, Json<serde_json::Value>)> {
+    let record_id = change_request
+        .record_id
+        .ok_or_else(|| not_found("The record this change request refers to no longer exists"))?;
+    let before = lock_record(&mut *conn, record_id).await?;
+    check_version(&before, change_request.base_version)?;
+    Ok(before)
+}
+
+// Applies the stored change as it was requested. If the record has been edited since,
+// the version check fails and the request has to be rejected and resubmitted.
+async fn apply(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    change_request: &ChangeRequest,
+) -> Result<Record, (StatusCode, Json<serde_json::Value>)> {
+    match change_request.action.as_str() {
+        "create" => {
+            let mut body: CreateRecordSchema = payload(change_request)?;
+            // Catalog entries may have been retired while the request was waiting.
+            catalogs::normalize_create(&mut *conn, &mut body).await?;
+            // The OR number is taken from the maker's series stream, not the approver's.
+            let maker = sqlx::query_as!(
+                User,
+                "SELECT * FROM \"users\" WHERE id = $1",
+                change_request.requested_by_id
+            )
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(db_error)?
+                .ok_or_else(|| not_found("The user who requested this change no longer exists"))?;
+            apply_create(conn, ctx, &maker, &body).await
+        }
+        "update" => {
+            let body: PatchRecordSchema = payload(change_request)?;
+            let before = target_record(conn, change_request).await?;
+            apply_patch(conn, ctx, &before, &body).await
+        }
+        _ => {
+            let reason = change_request.payload.get("reason").and_then(|reason| reason.as_str());
+            let before = target_record(conn, change_request).await?;
+            apply_delete(conn, ctx, &before, &change_request.requested_by, reason).await
+        }
+    }
+}
+
+pub async fn approve_change_request(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    body: Option<Json<DecisionSchema>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let note = body.and_then(|Json(body)| body.note);
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_pending(&mut tx, id, &user).await?;
+    let record = apply(&mut tx, &ctx, &before).await?;
+    let change_request = decide(&mut tx, &ctx, &user, &before, "approved", note.as_deref(), Some(record.id)).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "change_request": change_request,
+            "record": record
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn reject_change_request(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<DecisionSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let note = body.note.as_deref().map(str::trim).unwrap_or_default();
+    if note.is_empty() {
+        return Err(bad_request("A note explaining the rejection is required"));
+    }
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_pending(&mut tx, id, &user).await?;
+    let change_request = decide(&mut tx, &ctx, &user, &before, "rejected", Some(note), None).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "change_request": change_request
+        })
+    });
+
+    Ok(Json(json_response))
+}
@@ -0,0 +1,71 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct ApprovalRule {
+    pub id: i32,
+    pub name: String,
+    pub kind: String,
+    pub field: Option<String>,
+    pub threshold_days: Option<i32>,
+    pub applies_to_role: String,
+    pub approver_role: String,
+    pub active: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApprovalRuleSchema {
+    pub name: String,
+    pub kind: String,
+    pub field: Option<String>,
+    pub threshold_days: Option<i32>,
+    pub applies_to_role: Option<String>,
+    pub approver_role: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateApprovalRuleSchema {
+    pub name: Option<String>,
+    pub field: Option<String>,
+    pub threshold_days: Option<i32>,
+    pub applies_to_role: Option<String>,
+    pub approver_role: Option<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct ChangeRequest {
+    pub id: i32,
+    pub action: String,
+    pub record_id: Option<i32>,
+    pub base_version: Option<i32>,
+    pub payload: serde_json::Value,
+    pub matched_rules: serde_json::Value,
+    pub approver_role: String,
+    pub status: String,
+    pub requested_by_id: uuid::Uuid,
+    pub requested_by: String,
+    #[serde(rename = "requestedAt")]
+    pub requested_at: DateTime<Utc>,
+    pub decided_by: Option<String>,
+    #[serde(rename = "decidedAt")]
+    pub decided_at: Option<DateTime<Utc>>,
+    pub decision_note: Option<String>,
+    pub result_record_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeRequestQuery {
+    pub status: Option<String>,
+    pub record_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DecisionSchema {
+    pub note: Option<String>,
+}
@@ -0,0 +1,118 @@
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::{IntoResponse, Response};
+use chrono::prelude::*;
+use chrono::Duration;
+use serde_json::{json, Value};
+use sqlx::PgConnection;
+use crate::approval_model::{ApprovalRule, ChangeRequest};
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::model::User;
+use crate::record_model::Record;
+use crate::response::db_error;
+
+pub const RULE_KINDS: [&str; 3] = ["field_update", "delete", "backdated_create"];
+
+// What a request is about to do to the records table, as far as the rules care.
+pub enum ProposedChange<'a> {
+    Create { created_at: Option<DateTime<Utc>> },
+    Update { fields: &'a [&'a str] },
+    Delete,
+}
+
+// Either the change went through, or it is waiting in a change request.
+pub enum Outcome<T> {
+    Applied(T),
+    Pending(Box<ChangeRequest>),
+}
+
+fn matches(rule: &ApprovalRule, change: &ProposedChange<'_>) -> bool {
+    match (rule.kind.as_str(), change) {
+        ("field_update", ProposedChange::Update { fields }) => match &rule.field {
+            Some(field) => fields.contains(&field.as_str()),
+            None => !fields.is_empty(),
+        },
+        ("delete", ProposedChange::Delete) => true,
+        ("backdated_create", ProposedChange::Create { created_at: Some(created_at) }) => {
+            let threshold = Duration::days(rule.threshold_days.unwrap_or(0).into());
+            *created_at < Utc::now() - threshold
+        }
+        _ => false,
+    }
+}
+
+// Active rules that apply to this user's role and this change. Empty means the change
+// can be applied straight away.
+pub async fn matching_rules(
+    conn: &mut PgConnection,
+    user: &User,
+    change: &ProposedChange<'_>,
+) -> Result<Vec<ApprovalRule>, (StatusCode, Json<Value>)> {
+    let rules = sqlx::query_as!(
+        ApprovalRule,
+        "SELECT * FROM \"approval_rules\" WHERE active AND applies_to_role = $1 ORDER BY id",
+        user.role
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    Ok(rules.into_iter().filter(|rule| matches(rule, change)).collect())
+}
+
+// Parks the change instead of applying it. The first matching rule decides which
+// role may approve it.
+pub async fn submit(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    user: &User,
+    action: &str,
+    record: Option<&Record>,
+    payload: Value,
+    rules: &[ApprovalRule],
+) -> Result<ChangeRequest, (StatusCode, Json<Value>)> {
+    let matched_rules: Value = rules
+        .iter()
+        .map(|rule| json!({"id": rule.id, "name": rule.name}))
+        .collect();
+    let approver_role = rules
+        .first()
+        .map(|rule| rule.approver_role.to_owned())
+        .unwrap_or_else(|| "admin".to_string());
+
+    let change_request = sqlx::query_as!(
+        ChangeRequest,
+        "INSERT INTO \"change_requests\" (action,record_id,base_version,payload,matched_rules,approver_role,requested_by_id,requested_by) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *",
+        action,
+        record.map(|record| record.id),
+        record.map(|record| record.version),
+        payload,
+        matched_rules,
+        approver_role,
+        user.id,
+        user.username
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut *conn, ctx, "request", "change_request", &change_request.id.to_string(), None, Some(snapshot(&change_request)))
+        .await
+        .map_err(db_error)?;
+
+    Ok(change_request)
+}
+
+pub fn pending_body(change_request: &ChangeRequest) -> Value {
+    json!({
+        "status": "success",
+        "message": "Change submitted for approval",
+        "data": json!({
+            "change_request": change_request
+        })
+    })
+}
+
+pub fn pending_response(change_request: &ChangeRequest) -> Response {
+    (StatusCode::ACCEPTED, Json(pending_body(change_request))).into_response()
+}
@@ -0,0 +1,138 @@
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use axum::async_trait;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sqlx::PgConnection;
+use crate::model::User;
+use crate::AppState;
+
+// Who is making the current request and from where. Extracted once per request
+// and handed to `log_event` for every mutation the handler performs.
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub actor_id: Option<uuid::Uuid>,
+    pub actor_username: Option<String>,
+    pub ip: Option<String>,
+    pub request_id: String,
+}
+
+// The address the request came from. `X-Forwarded-For` is only believed when the
+// peer is one of our own proxies, and then it is read from the right so a client
+// cannot prepend an address of its choosing: the first hop that is not a trusted
+// proxy is the client.
+fn client_ip(parts: &Parts, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let peer = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())?;
+
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+
+    let forwarded: Vec<IpAddr> = parts
+        .headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|value| value.trim().parse::<IpAddr>())
+        .collect::<Result<_, _>>()
+        .unwrap_or_default();
+
+    let client = forwarded
+        .iter()
+        .rev()
+        .find(|ip| !trusted_proxies.contains(ip))
+        .or(forwarded.first())
+        .copied();
+
+    Some(client.unwrap_or(peer))
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AuditContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let user = parts.extensions.get::<User>();
+
+        let ip = client_ip(parts, &state.env.trusted_proxies).map(|ip| ip.to_string());
+
+        let request_id = parts
+            .headers
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        Ok(AuditContext {
+            actor_id: user.map(|user| user.id),
+            actor_username: user.map(|user| user.username.to_owned()),
+            ip,
+            request_id,
+        })
+    }
+}
+
+pub fn snapshot<T: Serialize>(entity: &T) -> Value {
+    serde_json::to_value(entity).unwrap_or(Value::Null)
+}
+
+// Field-level changes between two snapshots as `{"field": {"from": .., "to": ..}}`.
+// A missing side (create, purge) is treated as an empty object.
+pub fn diff(before: Option<&Value>, after: Option<&Value>) -> Value {
+    let empty = Map::new();
+    let before = before.and_then(Value::as_object).unwrap_or(&empty);
+    let after = after.and_then(Value::as_object).unwrap_or(&empty);
+
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys()) {
+        if changes.contains_key(key) {
+            continue;
+        }
+        let from = before.get(key).unwrap_or(&Value::Null);
+        let to = after.get(key).unwrap_or(&Value::Null);
+        if from != to {
+            changes.insert(key.to_owned(), json!({"from": from, "to": to}));
+        }
+    }
+
+    Value::Object(changes)
+}
+
+// Appends one row to `audit_events`. Callers pass the connection of the transaction
+// that performs the mutation so the event commits or rolls back together with it.
+pub async fn log_event(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    action: &str,
+    entity_type: &str,
+    entity_id: &str,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<(), sqlx::Error> {
+    let changes = diff(before.as_ref(), after.as_ref());
+
+    sqlx::query!(
+        "INSERT INTO \"audit_events\" (actor_id,actor_username,action,entity_type,entity_id,before,after,diff,ip,request_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        ctx.actor_id,
+        ctx.actor_username,
+        action,
+        entity_type,
+        entity_id,
+        before,
+        after,
+        changes,
+        ctx.ip,
+        ctx.request_id
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::IntoResponse;
+use serde_json::json;
+use sqlx::{Postgres, QueryBuilder};
+use crate::AppState;
+use crate::audit_model::{AuditEvent, AuditQuery};
+use crate::ledger;
+use crate::response::db_error;
+
+pub async fn get_audit_events(
+    State(data): State<Arc<AppState>>,
+    Query(params): Query<AuditQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM \"audit_events\" WHERE TRUE");
+    if let Some(entity_type) = params.entity_type {
+        query.push(" AND entity_type = ").push_bind(entity_type);
+    }
+    if let Some(entity_id) = params.entity_id {
+        query.push(" AND entity_id = ").push_bind(entity_id);
+    }
+    if let Some(actor_id) = params.actor_id {
+        query.push(" AND actor_id = ").push_bind(actor_id);
+    }
+    if let Some(action) = params.action {
+        query.push(" AND action = ").push_bind(action);
+    }
+    if let Some(from) = params.from {
+        query.push(" AND occurred_at >= ").push_bind(from);
+    }
+    if let Some(to) = params.to {
+        query.push(" AND occurred_at < ").push_bind(to);
+    }
+    query
+        .push(" ORDER BY occurred_at DESC, id DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let events: Vec<AuditEvent> = query
+        .build_query_as::<AuditEvent>()
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "events": events,
+            "limit": limit,
+            "offset": offset
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn get_record_history(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let events: Vec<AuditEvent> = sqlx::query_as!(
+        AuditEvent,
+        "SELECT * FROM \"audit_events\" WHERE entity_type = 'record' AND entity_id = $1 ORDER BY occurred_at, id",
+        id.to_string()
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "events": events
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn verify_ledger_handler(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let report = ledger::verify(&data.db).await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "report": report
+        })
+    });
+
+    Ok(Json(json_response))
+}
@@ -0,0 +1,186 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use axum::response::IntoResponse;
+use serde_json::{json, Value};
+use sqlx::{Acquire, Postgres, Transaction};
+use crate::AppState;
+use crate::approval_model::ChangeRequest;
+use crate::approvals::Outcome;
+use crate::audit::AuditContext;
+use crate::duplicates::DuplicateCheck;
+use crate::model::User;
+use crate::record_handlers::{stage_create, stage_delete, stage_patch};
+use crate::record_model::{BatchCreateSchema, BatchDeleteSchema, BatchMode, BatchUpdateSchema, Record};
+use crate::response::{bad_request, db_error};
+
+// Larger corrections should be split; one transaction holds every row lock it takes.
+const MAX_BATCH_ITEMS: usize = 500;
+
+enum ItemResult {
+    Applied(Box<Record>),
+    Pending(Box<ChangeRequest>),
+    Failed((StatusCode, Json<Value>)),
+}
+
+fn check_size(items: usize) -> Result<(), (StatusCode, Json<Value>)> {
+    if items == 0 {
+        return Err(bad_request("records must contain at least one item"));
+    }
+    if items > MAX_BATCH_ITEMS {
+        return Err(bad_request(&format!("A batch can hold at most {} items", MAX_BATCH_ITEMS)));
+    }
+    Ok(())
+}
+
+// Keeps or undoes one item's savepoint. A failed item never takes the others down with
+// it here; whether the batch as a whole is kept is decided in `finish`.
+async fn settle(
+    savepoint: Transaction<'_, Postgres>,
+    result: Result<Outcome<Record>, (StatusCode, Json<Value>)>,
+) -> Result<ItemResult, (StatusCode, Json<Value>)> {
+    match result {
+        Ok(outcome) => {
+            savepoint.commit().await.map_err(db_error)?;
+            Ok(match outcome {
+                Outcome::Applied(record) => ItemResult::Applied(Box::new(record)),
+                Outcome::Pending(change_request) => ItemResult::Pending(change_request),
+            })
+        }
+        Err(e) => {
+            savepoint.rollback().await.map_err(db_error)?;
+            Ok(ItemResult::Failed(e))
+        }
+    }
+}
+
+// Commits unless the batch is atomic and something failed. Items are reported in the
+// order they were sent; in a rolled back batch the ones that had gone through are
+// reported as `rolled_back`.
+async fn finish(
+    tx: Transaction<'_, Postgres>,
+    mode: BatchMode,
+    results: Vec<ItemResult>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let failed = results.iter().filter(|result| matches!(result, ItemResult::Failed(_))).count();
+    let committed = failed == 0 || mode == BatchMode::BestEffort;
+    if committed {
+        tx.commit().await.map_err(db_error)?;
+    } else {
+        tx.rollback().await.map_err(db_error)?;
+    }
+
+    let applied = results.iter().filter(|result| matches!(result, ItemResult::Applied(_))).count();
+    let pending = results.iter().filter(|result| matches!(result, ItemResult::Pending(_))).count();
+    let total = results.len();
+    let items: Vec<Value> = results
+        .into_iter()
+        .enumerate()
+        .map(|(index, result)| match result {
+            ItemResult::Failed((code, Json(error))) => json!({
+                "index": index,
+                "status": "failed",
+                "code": code.as_u16(),
+                "error": error
+            }),
+            _ if !committed => json!({"index": index, "status": "rolled_back"}),
+            ItemResult::Applied(record) => json!({"index": index, "status": "applied", "record": record}),
+            ItemResult::Pending(change_request) => json!({"index": index, "status": "pending", "change_request": change_request}),
+        })
+        .collect();
+
+    if !committed {
+        let error_response = json!({
+            "status": "fail",
+            "message": format!("{} of {} items failed; nothing was saved", failed, total),
+            "data": json!({
+                "mode": mode,
+                "results": items
+            })
+        });
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(error_response)));
+    }
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "mode": mode,
+            "applied": applied,
+            "pending": pending,
+            "failed": failed,
+            "results": items
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Each item goes through the same checks and approval rules as `POST /api/records`.
+pub async fn batch_create_records(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    ctx: AuditContext,
+    Json(body): Json<BatchCreateSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    check_size(body.records.len())?;
+
+    let duplicate_check = DuplicateCheck::new(&data.env, body.confirm_duplicates);
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+    let mut results = Vec::new();
+    for item in body.records {
+        let mut savepoint = tx.begin().await.map_err(db_error)?;
+        let result = stage_create(&mut savepoint, &ctx, &user, item, duplicate_check).await;
+        results.push(settle(savepoint, result).await?);
+    }
+
+    finish(tx, body.mode, results).await
+}
+
+// Each item goes through the same checks and approval rules as `PATCH /api/records/:id`.
+pub async fn batch_update_records(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    ctx: AuditContext,
+    Json(body): Json<BatchUpdateSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    check_size(body.records.len())?;
+
+    let duplicate_check = DuplicateCheck::new(&data.env, body.confirm_duplicates);
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+    let mut results = Vec::new();
+    for item in &body.records {
+        let mut savepoint = tx.begin().await.map_err(db_error)?;
+        let result = stage_patch(&mut savepoint, &ctx, &user, item.id, Some(item.version), &item.changes, duplicate_check).await;
+        results.push(settle(savepoint, result).await?);
+    }
+
+    finish(tx, body.mode, results).await
+}
+
+// Each item goes through the same checks and approval rules as `DELETE /api/records/:id`,
+// including the required reason.
+pub async fn batch_delete_records(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    ctx: AuditContext,
+    Json(body): Json<BatchDeleteSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    check_size(body.records.len())?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+    let mut results = Vec::new();
+    for item in &body.records {
+        let reason = item.reason.as_deref().map(str::trim).unwrap_or_default();
+        if reason.is_empty() {
+            results.push(ItemResult::Failed(bad_request("A reason is required to delete a record")));
+            continue;
+        }
+
+        let mut savepoint = tx.begin().await.map_err(db_error)?;
+        let result = stage_delete(&mut savepoint, &ctx, &user, item.id, Some(item.version), Some(reason)).await;
+        results.push(settle(savepoint, result).await?);
+    }
+
+    finish(tx, body.mode, results).await
+}
@@ -0,0 +1,368 @@
+use std::sync::Arc;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::IntoResponse;
+use serde_json::json;
+use sqlx::PgConnection;
+use crate::AppState;
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::catalog_model::{CatalogQuery, Course, CreateCourseSchema, CreatePaymentCategorySchema, CreateYearLevelSchema, PaymentCategory, UpdateCourseSchema, UpdatePaymentCategorySchema, UpdateYearLevelSchema, YearLevel};
+use crate::catalogs::normalize_amount;
+use crate::response::{conflict, db_error, not_found, required};
+
+// Entries are matched case-insensitively, so "bsit" would shadow "BSIT".
+async fn ensure_unique(
+    conn: &mut PgConnection,
+    table: &str,
+    column: &str,
+    value: &str,
+    except_id: Option<i32>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let exists: bool = sqlx::query_scalar(&format!(
+        "SELECT EXISTS(SELECT 1 FROM \"{table}\" WHERE LOWER({column}) = LOWER($1) AND id IS DISTINCT FROM $2)"
+    ))
+        .bind(value)
+        .bind(except_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    if exists {
+        return Err(conflict(&format!("'{}' already exists in {}", value, table)));
+    }
+    Ok(())
+}
+
+// Everything the record form needs for its dropdowns in one call.
+pub async fn get_catalogs(
+    State(data): State<Arc<AppState>>,
+    Query(params): Query<CatalogQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let include_inactive = params.include_inactive.unwrap_or(false);
+
+    let courses = sqlx::query_as!(
+        Course,
+        "SELECT * FROM \"courses\" WHERE active OR $1 ORDER BY code",
+        include_inactive
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let year_levels = sqlx::query_as!(
+        YearLevel,
+        "SELECT * FROM \"year_levels\" WHERE active OR $1 ORDER BY sort_order, code",
+        include_inactive
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let payment_categories = sqlx::query_as!(
+        PaymentCategory,
+        "SELECT * FROM \"payment_categories\" WHERE active OR $1 ORDER BY name",
+        include_inactive
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "courses": courses,
+            "year_levels": year_levels,
+            "payment_categories": payment_categories
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn create_course(
+    State(data): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Json(body): Json<CreateCourseSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let code = required("code", &body.code)?;
+    let name = match body.name.as_deref() {
+        Some(name) => required("name", name)?,
+        None => code.to_owned(),
+    };
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    ensure_unique(&mut tx, "courses", "code", &code, None).await?;
+
+    let course = sqlx::query_as!(
+        Course,
+        "INSERT INTO \"courses\" (code,name) VALUES ($1, $2) RETURNING *",
+        code,
+        name
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "create", "course", &course.id.to_string(), None, Some(snapshot(&course)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "course": course
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+// Renaming a code does not touch existing records; they keep the old value, which
+// stays valid on them until it is changed.
+pub async fn update_course(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<UpdateCourseSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let code = body.code.as_deref().map(|code| required("code", code)).transpose()?;
+    let name = body.name.as_deref().map(|name| required("name", name)).transpose()?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = sqlx::query_as!(
+        Course,
+        "SELECT * FROM \"courses\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No course found with the provided ID"))?;
+
+    if let Some(code) = &code {
+        ensure_unique(&mut tx, "courses", "code", code, Some(id)).await?;
+    }
+
+    let course = sqlx::query_as!(
+        Course,
+        "UPDATE \"courses\" SET code = COALESCE($1, code), name = COALESCE($2, name), active = COALESCE($3, active), updated_at = NOW() WHERE id = $4 RETURNING *",
+        code,
+        name,
+        body.active,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "update", "course", &id.to_string(), Some(snapshot(&before)), Some(snapshot(&course)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "course": course
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn create_year_level(
+    State(data): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Json(body): Json<CreateYearLevelSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let code = required("code", &body.code)?;
+    let name = match body.name.as_deref() {
+        Some(name) => required("name", name)?,
+        None => code.to_owned(),
+    };
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    ensure_unique(&mut tx, "year_levels", "code", &code, None).await?;
+
+    let year_level = sqlx::query_as!(
+        YearLevel,
+        "INSERT INTO \"year_levels\" (code,name,sort_order) VALUES ($1, $2, COALESCE($3, (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM \"year_levels\"))) RETURNING *",
+        code,
+        name,
+        body.sort_order
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "create", "year_level", &year_level.id.to_string(), None, Some(snapshot(&year_level)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "year_level": year_level
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+pub async fn update_year_level(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<UpdateYearLevelSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let code = body.code.as_deref().map(|code| required("code", code)).transpose()?;
+    let name = body.name.as_deref().map(|name| required("name", name)).transpose()?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = sqlx::query_as!(
+        YearLevel,
+        "SELECT * FROM \"year_levels\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No year level found with the provided ID"))?;
+
+    if let Some(code) = &code {
+        ensure_unique(&mut tx, "year_levels", "code", code, Some(id)).await?;
+    }
+
+    let year_level = sqlx::query_as!(
+        YearLevel,
+        "UPDATE \"year_levels\" SET code = COALESCE($1, code), name = COALESCE($2, name), sort_order = COALESCE($3, sort_order), active = COALESCE($4, active), updated_at = NOW() WHERE id = $5 RETURNING *",
+        code,
+        name,
+        body.sort_order,
+        body.active,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "update", "year_level", &id.to_string(), Some(snapshot(&before)), Some(snapshot(&year_level)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "year_level": year_level
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn create_payment_category(
+    State(data): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Json(body): Json<CreatePaymentCategorySchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let name = required("name", &body.name)?;
+    let default_amount = body.default_amount.as_deref().map(|value| normalize_amount("default_amount", value)).transpose()?;
+    let gl_code = body.gl_code.as_deref().map(|gl_code| required("gl_code", gl_code)).transpose()?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    ensure_unique(&mut tx, "payment_categories", "name", &name, None).await?;
+
+    let category = sqlx::query_as!(
+        PaymentCategory,
+        "INSERT INTO \"payment_categories\" (name,default_amount,gl_code) VALUES ($1, $2, $3) RETURNING *",
+        name,
+        default_amount,
+        gl_code
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "create", "payment_category", &category.id.to_string(), None, Some(snapshot(&category)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "payment_category": category
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+pub async fn update_payment_category(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<UpdatePaymentCategorySchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let name = body.name.as_deref().map(|name| required("name", name)).transpose()?;
+    let default_amount = body.default_amount.as_deref().map(|value| normalize_amount("default_amount", value)).transpose()?;
+    let gl_code = body.gl_code.as_deref().map(|gl_code| required("gl_code", gl_code)).transpose()?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = sqlx::query_as!(
+        PaymentCategory,
+        "SELECT * FROM \"payment_categories\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No payment category found with the provided ID"))?;
+
+    if let Some(name) = &name {
+        ensure_unique(&mut tx, "payment_categories", "name", name, Some(id)).await?;
+    }
+
+    let category = sqlx::query_as!(
+        PaymentCategory,
+        "UPDATE \"payment_categories\" SET name = COALESCE($1, name), default_amount = COALESCE($2, default_amount), gl_code = COALESCE($3, gl_code), active = COALESCE($4, active), updated_at = NOW() WHERE id = $5 RETURNING *",
+        name,
+        default_amount,
+        gl_code,
+        body.active,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "update", "payment_category", &id.to_string(), Some(snapshot(&before)), Some(snapshot(&category)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "payment_category": category
+        })
+    });
+
+    Ok(Json(json_response))
+}
@@ -0,0 +1,93 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct Course {
+    pub id: i32,
+    // The value stored in `records.course`.
+    pub code: String,
+    pub name: String,
+    pub active: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct YearLevel {
+    pub id: i32,
+    // The value stored in `records.year_level`.
+    pub code: String,
+    pub name: String,
+    pub sort_order: i32,
+    pub active: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct PaymentCategory {
+    pub id: i32,
+    // The value stored in `records.payment_for`.
+    pub name: String,
+    pub default_amount: Option<String>,
+    pub gl_code: Option<String>,
+    pub active: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCourseSchema {
+    pub code: String,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCourseSchema {
+    pub code: Option<String>,
+    pub name: Option<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateYearLevelSchema {
+    pub code: String,
+    pub name: Option<String>,
+    pub sort_order: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateYearLevelSchema {
+    pub code: Option<String>,
+    pub name: Option<String>,
+    pub sort_order: Option<i32>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePaymentCategorySchema {
+    pub name: String,
+    pub default_amount: Option<String>,
+    pub gl_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePaymentCategorySchema {
+    pub name: Option<String>,
+    pub default_amount: Option<String>,
+    pub gl_code: Option<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogQuery {
+    // Inactive entries are hidden from dropdowns unless asked for.
+    pub include_inactive: Option<bool>,
+}
@@ -0,0 +1,80 @@
+use axum::http::StatusCode;
+use axum::Json;
+use serde_json::Value;
+use sqlx::PgConnection;
+use crate::amount;
+use crate::catalog_model::PaymentCategory;
+use crate::record_model::CreateRecordSchema;
+use crate::response::{bad_request, db_error};
+
+// Record column, catalog table and the catalog column whose value the record stores.
+const CATALOGS: [(&str, &str, &str); 3] = [
+    ("course", "courses", "code"),
+    ("year_level", "year_levels", "code"),
+    ("payment_for", "payment_categories", "name"),
+];
+
+// The catalog's spelling of `value` for a record column, e.g. "bsit" becomes "BSIT".
+// Columns without a catalog are returned unchanged.
+pub async fn canonical(
+    conn: &mut PgConnection,
+    column: &str,
+    value: &str,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    let Some((_, table, key)) = CATALOGS.iter().find(|(record_column, _, _)| *record_column == column) else {
+        return Ok(value.to_string());
+    };
+
+    let found: Option<String> = sqlx::query_scalar(&format!(
+        "SELECT {key} FROM \"{table}\" WHERE LOWER({key}) = LOWER(BTRIM($1)) AND active"
+    ))
+        .bind(value)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    found.ok_or_else(|| bad_request(&format!("Unknown {}: '{}' is not an active entry in the catalog", column, value)))
+}
+
+async fn payment_category(
+    conn: &mut PgConnection,
+    name: &str,
+) -> Result<PaymentCategory, (StatusCode, Json<Value>)> {
+    let name = canonical(&mut *conn, "payment_for", name).await?;
+    sqlx::query_as!(
+        PaymentCategory,
+        "SELECT * FROM \"payment_categories\" WHERE name = $1",
+        name
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)
+}
+
+// Rewrites a new payment's catalog fields to their canonical spelling and fills in the
+// category's default amount when none was given.
+pub async fn normalize_create(
+    conn: &mut PgConnection,
+    body: &mut CreateRecordSchema,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    body.course = canonical(&mut *conn, "course", &body.course).await?;
+    body.year_level = canonical(&mut *conn, "year_level", &body.year_level).await?;
+
+    let category = payment_category(&mut *conn, &body.payment_for).await?;
+    body.payment_for = category.name;
+    if body.amount.trim().is_empty() {
+        body.amount = category
+            .default_amount
+            .ok_or_else(|| bad_request(&format!("Amount is required; {} has no default amount", body.payment_for)))?;
+    }
+
+    Ok(())
+}
+
+// Catalog and fee amounts are stored in the same format the API writes amounts in.
+pub fn normalize_amount(field: &str, value: &str) -> Result<String, (StatusCode, Json<Value>)> {
+    amount::parse_cents(value)
+        .filter(|cents| *cents >= 0)
+        .map(amount::format_cents)
+        .ok_or_else(|| bad_request(&format!("{} must be a non-negative number", field)))
+}
@@ -1,3 +1,4 @@
+use std::net::IpAddr;
 use dotenv::dotenv;
 
 #[derive(Debug, Clone)]
@@ -5,8 +6,19 @@ pub struct Config {
     pub database_url: String,
     pub port: String,
     pub jwt_secret: String,
-    pub jwt_expires_in: String,
-    pub jwt_maxage: i32
+    // Minutes a login token and its cookie stay valid.
+    pub jwt_maxage: i32,
+    pub legacy_routes: bool,
+    pub trash_retention_days: i64,
+    pub receipt_template_path: Option<String>,
+    pub public_base_url: String,
+    pub export_dir: String,
+    pub export_ttl_minutes: i64,
+    pub idempotency_ttl_hours: i64,
+    // 0 turns the duplicate payment check off.
+    pub duplicate_window_hours: i64,
+    // Peers whose `X-Forwarded-For` header is believed when auditing.
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl Config {
@@ -15,16 +27,39 @@ impl Config {
 
         let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
         let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-        let jwt_expires_in = std::env::var("JWT_EXPIRED_IN").expect("JWT_EXPIRED_IN must be set");
         let jwt_maxage = std::env::var("JWT_MAXAGE").expect("JWT_MAXAGE must be set");
         let port = dotenv::var("PORT").unwrap_or_else(|_| "3000".to_string());
+        let legacy_routes = std::env::var("ENABLE_LEGACY_ROUTES").unwrap_or_else(|_| "true".to_string());
+        let trash_retention_days = std::env::var("TRASH_RETENTION_DAYS").unwrap_or_else(|_| "30".to_string());
+        let receipt_template_path = std::env::var("RECEIPT_TEMPLATE_PATH").ok();
+        // Printed into receipt QR codes, so it has to be reachable by whoever scans them.
+        let public_base_url = std::env::var("PUBLIC_BASE_URL").unwrap_or_else(|_| format!("http://localhost:{}", port));
+        let export_dir = std::env::var("EXPORT_DIR")
+            .unwrap_or_else(|_| std::env::temp_dir().join("rust-api-exports").to_string_lossy().to_string());
+        let export_ttl_minutes = std::env::var("EXPORT_TTL_MINUTES").unwrap_or_else(|_| "60".to_string());
+        let idempotency_ttl_hours = std::env::var("IDEMPOTENCY_TTL_HOURS").unwrap_or_else(|_| "24".to_string());
+        let duplicate_window_hours = std::env::var("DUPLICATE_WINDOW_HOURS").unwrap_or_else(|_| "24".to_string());
+        let trusted_proxies = std::env::var("TRUSTED_PROXIES").unwrap_or_default();
 
         Config {
             database_url,
             port,
             jwt_secret,
-            jwt_expires_in,
             jwt_maxage: jwt_maxage.parse::<i32>().unwrap(),
+            legacy_routes: legacy_routes.parse::<bool>().expect("ENABLE_LEGACY_ROUTES must be true or false"),
+            trash_retention_days: trash_retention_days.parse::<i64>().expect("TRASH_RETENTION_DAYS must be a whole number of days"),
+            receipt_template_path,
+            public_base_url: public_base_url.trim_end_matches('/').to_string(),
+            export_dir,
+            export_ttl_minutes: export_ttl_minutes.parse::<i64>().expect("EXPORT_TTL_MINUTES must be a whole number of minutes"),
+            idempotency_ttl_hours: idempotency_ttl_hours.parse::<i64>().expect("IDEMPOTENCY_TTL_HOURS must be a whole number of hours"),
+            duplicate_window_hours: duplicate_window_hours.parse::<i64>().expect("DUPLICATE_WINDOW_HOURS must be a whole number of hours"),
+            trusted_proxies: trusted_proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(|proxy| proxy.parse::<IpAddr>().expect("TRUSTED_PROXIES must be a comma-separated list of IP addresses"))
+                .collect(),
         }
     }
 }
\ No newline at end of file
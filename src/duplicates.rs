@@ -0,0 +1,105 @@
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sqlx::PgConnection;
+use crate::config::Config;
+use crate::record_model::Record;
+use crate::response::db_error;
+
+// At most this many earlier payments are listed in a warning.
+const MAX_LISTED: i64 = 10;
+
+// Whether a create or update should stop at a suspected duplicate.
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateCheck {
+    window_hours: i64,
+    confirmed: bool,
+}
+
+impl DuplicateCheck {
+    pub fn new(env: &Config, confirmed: bool) -> Self {
+        DuplicateCheck {
+            window_hours: env.duplicate_window_hours,
+            confirmed,
+        }
+    }
+}
+
+// The payment being written, as the duplicate check sees it.
+pub struct Candidate<'a> {
+    // The record itself, when it is being updated.
+    pub id: Option<i32>,
+    pub student_id: Option<i32>,
+    pub first_name: &'a str,
+    pub last_name: &'a str,
+    pub payment_for: &'a str,
+    pub amount: &'a str,
+    // Defaults to now.
+    pub at: Option<DateTime<Utc>>,
+}
+
+// Live payments of the same category and amount, for the same student or a student of
+// the same name, within the window either side of the payment. Payments that have
+// been voided are not counted.
+pub async fn find(
+    conn: &mut PgConnection,
+    window_hours: i64,
+    candidate: &Candidate<'_>,
+) -> Result<Vec<Record>, (StatusCode, Json<Value>)> {
+    sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" r
+        WHERE r.deleted_at IS NULL AND r.entry_type = 'payment'
+            AND r.id IS DISTINCT FROM $1
+            AND r.payment_for = $2
+            AND record_amount(r.amount) = record_amount($3)
+            AND (r.student_id = $4 OR (student_name_key(r.first_name) = student_name_key($5) AND student_name_key(r.last_name) = student_name_key($6)))
+            AND r.created_at BETWEEN COALESCE($7, NOW()) - make_interval(hours => $8) AND COALESCE($7, NOW()) + make_interval(hours => $8)
+            AND NOT EXISTS (SELECT 1 FROM \"records\" v WHERE v.reverses_record_id = r.id AND v.entry_type = 'void')
+        ORDER BY r.created_at
+        LIMIT $9",
+        candidate.id,
+        candidate.payment_for,
+        candidate.amount,
+        candidate.student_id,
+        candidate.first_name,
+        candidate.last_name,
+        candidate.at,
+        window_hours as i32,
+        MAX_LISTED
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)
+}
+
+// Stops with a 409 listing the earlier payments, unless the caller already confirmed.
+pub async fn check(
+    conn: &mut PgConnection,
+    check: DuplicateCheck,
+    candidate: &Candidate<'_>,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    if check.confirmed || check.window_hours <= 0 {
+        return Ok(());
+    }
+
+    let duplicates = find(conn, check.window_hours, candidate).await?;
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+
+    let error_response = json!({
+        "status": "fail",
+        "message": format!(
+            "This looks like a duplicate of {} payment(s) for {} within {} hours; confirm it to record it anyway",
+            duplicates.len(),
+            candidate.payment_for,
+            check.window_hours
+        ),
+        "data": json!({
+            "duplicates": duplicates
+        })
+    });
+    Err((StatusCode::CONFLICT, Json(error_response)))
+}
@@ -0,0 +1,62 @@
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::Json;
+use serde_json::json;
+
+pub enum IfMatch {
+    Missing,
+    Any,
+    Version(i32),
+}
+
+impl IfMatch {
+    // Writes on the per-resource routes must say which version they were based on.
+    pub fn required(self) -> Result<Option<i32>, (StatusCode, Json<serde_json::Value>)> {
+        match self {
+            IfMatch::Missing => {
+                let error_response = json!({
+                    "status": "fail",
+                    "message": "If-Match header with the record's ETag is required",
+                });
+                Err((StatusCode::PRECONDITION_REQUIRED, Json(error_response)))
+            }
+            IfMatch::Any => Ok(None),
+            IfMatch::Version(version) => Ok(Some(version)),
+        }
+    }
+
+    pub fn optional(self) -> Option<i32> {
+        match self {
+            IfMatch::Version(version) => Some(version),
+            IfMatch::Missing | IfMatch::Any => None,
+        }
+    }
+}
+
+pub fn etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
+pub fn if_match(headers: &HeaderMap) -> Result<IfMatch, (StatusCode, Json<serde_json::Value>)> {
+    let value = match headers.get(header::IF_MATCH) {
+        Some(value) => value,
+        None => return Ok(IfMatch::Missing),
+    };
+
+    let value = value.to_str().unwrap_or_default().trim();
+    if value == "*" {
+        return Ok(IfMatch::Any);
+    }
+
+    value
+        .trim_start_matches("W/")
+        .trim_matches('"')
+        .parse::<i32>()
+        .map(IfMatch::Version)
+        .map_err(|_| {
+            let error_response = json!({
+                "status": "fail",
+                "message": format!("Invalid If-Match header: {}", value),
+            });
+            (StatusCode::BAD_REQUEST, Json(error_response))
+        })
+}
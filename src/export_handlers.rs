@@ -0,0 +1,61 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use axum::response::IntoResponse;
+use serde_json::json;
+use crate::AppState;
+use crate::export_model::ExportJob;
+use crate::exports;
+use crate::model::User;
+use crate::response::db_error;
+
+pub async fn get_exports(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let exports = sqlx::query_as!(
+        ExportJob,
+        "SELECT * FROM \"export_jobs\" WHERE owner_id = $1 ORDER BY created_at DESC",
+        user.id
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "exports": exports
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn get_export(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let export = exports::find_owned(&data.db, &user, id).await?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "export": export,
+            "download_url": format!("/api/exports/{}/download", export.id)
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn download_export(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let export = exports::find_owned(&data.db, &user, id).await?;
+    exports::download(&export).await
+}
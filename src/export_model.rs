@@ -0,0 +1,21 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct ExportJob {
+    pub id: uuid::Uuid,
+    pub owner_id: uuid::Uuid,
+    // What was exported, e.g. "records".
+    pub kind: String,
+    pub filename: String,
+    #[serde(skip_serializing)]
+    pub file_path: String,
+    // "ready" while the file can be downloaded, "expired" once it has been removed.
+    pub status: String,
+    pub row_count: i32,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: DateTime<Utc>,
+}
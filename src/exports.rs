@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use axum::body::Body;
+use axum::http::{Response, StatusCode};
+use axum::Json;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tokio::fs::File;
+use uuid::Uuid;
+use crate::config::Config;
+use crate::export_model::ExportJob;
+use crate::model::User;
+use crate::record_handlers::xlsx_response;
+use crate::response::{db_error, not_found};
+
+// How often expired export files are looked for.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+fn file_error(e: std::io::Error) -> (StatusCode, Json<Value>) {
+    let error_response = json!({
+        "status": "fail",
+        "message": format!("File error: {}", e),
+    });
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+}
+
+pub struct ExportFile {
+    pub id: Uuid,
+    pub path: PathBuf,
+}
+
+// Picks the id and path for a new export. Files are named after the job, so concurrent
+// exports never share one.
+pub async fn prepare(env: &Config, extension: &str) -> Result<ExportFile, (StatusCode, Json<Value>)> {
+    tokio::fs::create_dir_all(&env.export_dir).await.map_err(file_error)?;
+    let id = Uuid::new_v4();
+    Ok(ExportFile {
+        id,
+        path: PathBuf::from(&env.export_dir).join(format!("{}.{}", id, extension)),
+    })
+}
+
+// Removes the file of an export that failed before it was registered, since cleanup
+// only knows about registered ones.
+pub async fn discard(file: &ExportFile) {
+    let _ = tokio::fs::remove_file(&file.path).await;
+}
+
+// Records a finished export file so its owner can download it until it expires.
+pub async fn register(
+    db: &PgPool,
+    env: &Config,
+    owner: &User,
+    file: &ExportFile,
+    kind: &str,
+    filename: &str,
+    row_count: usize,
+) -> Result<ExportJob, (StatusCode, Json<Value>)> {
+    sqlx::query_as!(
+        ExportJob,
+        "INSERT INTO \"export_jobs\" (id,owner_id,kind,filename,file_path,row_count,expires_at) VALUES ($1, $2, $3, $4, $5, $6, NOW() + make_interval(mins => $7)) RETURNING *",
+        file.id,
+        owner.id,
+        kind,
+        filename,
+        file.path.to_string_lossy().to_string(),
+        row_count as i32,
+        env.export_ttl_minutes as i32
+    )
+        .fetch_one(db)
+        .await
+        .map_err(db_error)
+}
+
+// Someone else's export is reported as missing rather than forbidden, so ids cannot be
+// probed.
+pub async fn find_owned(
+    db: &PgPool,
+    owner: &User,
+    id: Uuid,
+) -> Result<ExportJob, (StatusCode, Json<Value>)> {
+    sqlx::query_as!(
+        ExportJob,
+        "SELECT * FROM \"export_jobs\" WHERE id = $1 AND owner_id = $2",
+        id,
+        owner.id
+    )
+        .fetch_optional(db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No export found with the provided ID"))
+}
+
+pub async fn download(job: &ExportJob) -> Result<Response<Body>, (StatusCode, Json<Value>)> {
+    if job.status != "ready" || job.expires_at <= chrono::Utc::now() {
+        let error_response = json!({
+            "status": "fail",
+            "message": "This export has expired; run it again",
+        });
+        return Err((StatusCode::GONE, Json(error_response)));
+    }
+
+    let file = File::open(&job.file_path).await.map_err(file_error)?;
+    Ok(xlsx_response(file, &job.filename))
+}
+
+// A scratch path for a file that is written, streamed once and never kept.
+pub fn temp_path(extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("export-{}.{}", Uuid::new_v4(), extension))
+}
+
+// Streams a scratch workbook back as `filename`. The open handle keeps the file readable
+// after it is unlinked, so nothing is left behind once the download finishes.
+pub async fn stream_temp(path: &std::path::Path, filename: &str) -> Result<Response<Body>, (StatusCode, Json<Value>)> {
+    let file = File::open(path).await.map_err(file_error);
+    let _ = tokio::fs::remove_file(path).await;
+    Ok(xlsx_response(file?, filename))
+}
+
+// Marks expired jobs and deletes their files. A file that is already gone is fine.
+pub async fn cleanup(db: &PgPool) -> Result<usize, sqlx::Error> {
+    let paths = sqlx::query_scalar!(
+        "UPDATE \"export_jobs\" SET status = 'expired' WHERE status = 'ready' AND expires_at <= NOW() RETURNING file_path"
+    )
+        .fetch_all(db)
+        .await?;
+
+    for path in &paths {
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                println!("Could not remove expired export {}: {:?}", path, e);
+            }
+        }
+    }
+
+    Ok(paths.len())
+}
+
+pub fn spawn_cleanup(db: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = cleanup(&db).await {
+                println!("Export cleanup failed: {:?}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,412 @@
+use std::sync::Arc;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use axum::response::IntoResponse;
+use serde_json::json;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use crate::AppState;
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::catalogs::{self, normalize_amount};
+use crate::fee_model::{AssessSchema, Assessment, BalanceQuery, BalanceRow, CreateFeeScheduleSchema, FeeItemSchema, FeeSchedule, FeeScheduleItem, FeeScheduleQuery, FeeScheduleWithItems, UpdateFeeScheduleSchema};
+use crate::model::User;
+use crate::response::{bad_request, conflict, db_error, not_found};
+use crate::student_model::Student;
+use crate::terms::{self, validate_school_year, validate_semester};
+
+// Canonical category names and amounts, with each category at most once.
+async fn validate_items(
+    conn: &mut PgConnection,
+    items: &[FeeItemSchema],
+) -> Result<Vec<FeeItemSchema>, (StatusCode, Json<serde_json::Value>)> {
+    if items.is_empty() {
+        return Err(bad_request("A fee schedule needs at least one item"));
+    }
+
+    let mut validated: Vec<FeeItemSchema> = Vec::with_capacity(items.len());
+    for item in items {
+        let payment_for = catalogs::canonical(&mut *conn, "payment_for", &item.payment_for).await?;
+        if validated.iter().any(|existing| existing.payment_for == payment_for) {
+            return Err(bad_request(&format!("{} is listed more than once", payment_for)));
+        }
+        validated.push(FeeItemSchema {
+            payment_for,
+            amount: normalize_amount("amount", &item.amount)?,
+        });
+    }
+    Ok(validated)
+}
+
+async fn insert_items(
+    conn: &mut PgConnection,
+    fee_schedule_id: i32,
+    items: &[FeeItemSchema],
+) -> Result<Vec<FeeScheduleItem>, (StatusCode, Json<serde_json::Value>)> {
+    let mut inserted = Vec::with_capacity(items.len());
+    for item in items {
+        let row = sqlx::query_as!(
+            FeeScheduleItem,
+            "INSERT INTO \"fee_schedule_items\" (fee_schedule_id,payment_for,amount) VALUES ($1, $2, $3) RETURNING *",
+            fee_schedule_id,
+            item.payment_for,
+            item.amount
+        )
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(db_error)?;
+        inserted.push(row);
+    }
+    Ok(inserted)
+}
+
+async fn schedule_items(
+    conn: &mut PgConnection,
+    fee_schedule_id: i32,
+) -> Result<Vec<FeeScheduleItem>, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as!(
+        FeeScheduleItem,
+        "SELECT * FROM \"fee_schedule_items\" WHERE fee_schedule_id = $1 ORDER BY payment_for",
+        fee_schedule_id
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)
+}
+
+async fn lock_schedule(
+    conn: &mut PgConnection,
+    id: i32,
+) -> Result<FeeSchedule, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as!(
+        FeeSchedule,
+        "SELECT * FROM \"fee_schedules\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No fee schedule found with the provided ID"))
+}
+
+pub async fn get_fee_schedules(
+    State(data): State<Arc<AppState>>,
+    Query(params): Query<FeeScheduleQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut query = QueryBuilder::<Postgres>::new(
+        "SELECT f.* FROM \"fee_schedules\" f LEFT JOIN \"academic_terms\" t ON t.id = f.academic_term_id WHERE TRUE"
+    );
+    if let Some(academic_term_id) = params.academic_term_id {
+        query.push(" AND f.academic_term_id = ").push_bind(academic_term_id);
+    }
+    if let Some(school_year) = &params.school_year {
+        query.push(" AND t.school_year = ").push_bind(validate_school_year(school_year)?);
+    }
+    if let Some(semester) = &params.semester {
+        query.push(" AND t.semester = ").push_bind(validate_semester(semester)?);
+    }
+    if let Some(course) = params.course {
+        query.push(" AND f.course = ").push_bind(course);
+    }
+    if let Some(year_level) = params.year_level {
+        query.push(" AND f.year_level = ").push_bind(year_level);
+    }
+    query.push(" ORDER BY t.start_date DESC NULLS FIRST, f.course, f.year_level");
+
+    let schedules: Vec<FeeSchedule> = query
+        .build_query_as::<FeeSchedule>()
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let ids: Vec<i32> = schedules.iter().map(|schedule| schedule.id).collect();
+    let items = sqlx::query_as!(
+        FeeScheduleItem,
+        "SELECT * FROM \"fee_schedule_items\" WHERE fee_schedule_id = ANY($1) ORDER BY payment_for",
+        &ids
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let schedules: Vec<FeeScheduleWithItems> = schedules
+        .into_iter()
+        .map(|schedule| FeeScheduleWithItems {
+            items: items.iter().filter(|item| item.fee_schedule_id == schedule.id).cloned().collect(),
+            schedule,
+        })
+        .collect();
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "fee_schedules": schedules
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn create_fee_schedule(
+    State(data): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Json(body): Json<CreateFeeScheduleSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let term = terms::check_term(&mut tx, body.academic_term_id).await?;
+
+    let course = catalogs::canonical(&mut tx, "course", &body.course).await?;
+    let year_level = catalogs::canonical(&mut tx, "year_level", &body.year_level).await?;
+    let items = validate_items(&mut tx, &body.items).await?;
+
+    let exists = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM \"fee_schedules\" WHERE academic_term_id = $1 AND course = $2 AND year_level = $3)",
+        term.id,
+        course,
+        year_level
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .unwrap_or(false);
+    if exists {
+        return Err(conflict(&format!(
+            "A fee schedule for {} {} in the {} semester of {} already exists",
+            course, year_level, term.semester, term.school_year
+        )));
+    }
+
+    let schedule = sqlx::query_as!(
+        FeeSchedule,
+        "INSERT INTO \"fee_schedules\" (academic_term_id,course,year_level) VALUES ($1, $2, $3) RETURNING *",
+        term.id,
+        course,
+        year_level
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    let items = insert_items(&mut tx, schedule.id, &items).await?;
+
+    let schedule = FeeScheduleWithItems { schedule, items };
+    log_event(&mut tx, &ctx, "create", "fee_schedule", &schedule.schedule.id.to_string(), None, Some(snapshot(&schedule)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "fee_schedule": schedule
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+pub async fn update_fee_schedule(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<UpdateFeeScheduleSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let schedule = lock_schedule(&mut tx, id).await?;
+    let items = schedule_items(&mut tx, id).await?;
+    let before = FeeScheduleWithItems { schedule, items };
+
+    let schedule = sqlx::query_as!(
+        FeeSchedule,
+        "UPDATE \"fee_schedules\" SET active = COALESCE($1, active), updated_at = NOW() WHERE id = $2 RETURNING *",
+        body.active,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    let items = match &body.items {
+        Some(items) => {
+            let items = validate_items(&mut tx, items).await?;
+            sqlx::query!("DELETE FROM \"fee_schedule_items\" WHERE fee_schedule_id = $1", id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+            insert_items(&mut tx, id, &items).await?
+        }
+        None => before.items.clone(),
+    };
+
+    let schedule = FeeScheduleWithItems { schedule, items };
+    log_event(&mut tx, &ctx, "update", "fee_schedule", &id.to_string(), Some(snapshot(&before)), Some(snapshot(&schedule)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "fee_schedule": schedule
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Bills the schedule's items to students for its term. Running it again only adds what
+// is missing, so newly enrolled students can be assessed later without double billing.
+pub async fn assess_fee_schedule(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    body: Option<Json<AssessSchema>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let student_ids = body.and_then(|Json(body)| body.student_ids);
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let schedule = lock_schedule(&mut tx, id).await?;
+    if !schedule.active {
+        return Err(conflict("Fee schedule is inactive"));
+    }
+    let academic_term_id = schedule.academic_term_id.ok_or_else(|| conflict(&format!(
+        "Fee schedule is not linked to an academic term; link its term {:?} first",
+        schedule.legacy_term.as_deref().unwrap_or_default()
+    )))?;
+
+    let students = match &student_ids {
+        Some(ids) => {
+            let students = sqlx::query_as!(
+                Student,
+                "SELECT * FROM \"students\" WHERE id = ANY($1) ORDER BY id",
+                ids
+            )
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(db_error)?;
+            if let Some(missing) = ids.iter().find(|id| !students.iter().any(|student| student.id == **id)) {
+                return Err(bad_request(&format!("No student found with ID {}", missing)));
+            }
+            students
+        }
+        None => sqlx::query_as!(
+            Student,
+            "SELECT * FROM \"students\" WHERE status = 'active' AND course = $1 AND year_level = $2 ORDER BY id",
+            schedule.course,
+            schedule.year_level
+        )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(db_error)?,
+    };
+    let ids: Vec<i32> = students.iter().map(|student| student.id).collect();
+
+    let created = sqlx::query_as!(
+        Assessment,
+        "INSERT INTO \"assessments\" (student_id,academic_term_id,payment_for,amount,fee_schedule_id,created_by)
+        SELECT s.id, $2, i.payment_for, i.amount, $1, $4
+        FROM UNNEST($3::INTEGER[]) AS s(id)
+        CROSS JOIN \"fee_schedule_items\" i
+        WHERE i.fee_schedule_id = $1
+        ON CONFLICT (student_id, academic_term_id, payment_for) DO NOTHING
+        RETURNING *",
+        schedule.id,
+        academic_term_id,
+        &ids,
+        user.username
+    )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "assess", "fee_schedule", &schedule.id.to_string(), None, Some(json!({
+        "students": ids,
+        "assessments_created": created.len()
+    })))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "students": ids.len(),
+            "assessments": created
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Payments are applied by `payment_for`: everything a student has paid toward a
+// category, net of voids and refunds, counts against what they were assessed for it.
+// With `academic_term_id`, only that term's assessments and payments are counted.
+pub async fn get_student_balance(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Query(params): Query<BalanceQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let student = sqlx::query_as!(
+        Student,
+        "SELECT * FROM \"students\" WHERE id = $1",
+        id
+    )
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No student found with the provided ID"))?;
+
+    let assessments = sqlx::query_as!(
+        Assessment,
+        "SELECT a.* FROM \"assessments\" a LEFT JOIN \"academic_terms\" t ON t.id = a.academic_term_id
+        WHERE a.student_id = $1 AND ($2::INTEGER IS NULL OR a.academic_term_id = $2)
+        ORDER BY t.start_date NULLS FIRST, a.payment_for",
+        id,
+        params.academic_term_id
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    // The ROLLUP row, with no payment_for, carries the totals.
+    let mut rows: Vec<BalanceRow> = sqlx::query_as::<_, BalanceRow>(
+        "WITH lines AS (
+            SELECT payment_for, record_amount(amount) AS assessed, 0::NUMERIC AS paid
+            FROM \"assessments\" WHERE student_id = $1 AND ($2::INTEGER IS NULL OR academic_term_id = $2)
+            UNION ALL
+            SELECT payment_for, 0::NUMERIC, record_amount(amount)
+            FROM \"records\" WHERE student_id = $1 AND deleted_at IS NULL AND ($2::INTEGER IS NULL OR academic_term_id = $2)
+        )
+        SELECT payment_for,
+            COALESCE(SUM(assessed), 0)::NUMERIC(14, 2)::TEXT AS assessed,
+            COALESCE(SUM(paid), 0)::NUMERIC(14, 2)::TEXT AS paid,
+            (COALESCE(SUM(assessed), 0) - COALESCE(SUM(paid), 0))::NUMERIC(14, 2)::TEXT AS outstanding
+        FROM lines
+        GROUP BY ROLLUP (payment_for)
+        ORDER BY payment_for NULLS LAST"
+    )
+        .bind(id)
+        .bind(params.academic_term_id)
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let totals = rows.pop();
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "student": student,
+            "rows": rows,
+            "totals": totals,
+            "assessments": assessments
+        })
+    });
+
+    Ok(Json(json_response))
+}
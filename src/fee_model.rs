@@ -0,0 +1,100 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct FeeSchedule {
+    pub id: i32,
+    pub course: String,
+    pub year_level: String,
+    pub active: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+    // Unset only on schedules whose free-text term could not be matched when terms
+    // became a table; `legacy_term` holds that text until an admin links it.
+    pub academic_term_id: Option<i32>,
+    pub legacy_term: Option<String>,
+}
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct FeeScheduleItem {
+    pub id: i32,
+    pub fee_schedule_id: i32,
+    pub payment_for: String,
+    pub amount: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeeScheduleWithItems {
+    #[serde(flatten)]
+    pub schedule: FeeSchedule,
+    pub items: Vec<FeeScheduleItem>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FeeItemSchema {
+    pub payment_for: String,
+    pub amount: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFeeScheduleSchema {
+    pub academic_term_id: i32,
+    pub course: String,
+    pub year_level: String,
+    pub items: Vec<FeeItemSchema>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateFeeScheduleSchema {
+    pub active: Option<bool>,
+    // Replaces every item; assessments already generated keep their amounts.
+    pub items: Option<Vec<FeeItemSchema>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeeScheduleQuery {
+    pub academic_term_id: Option<i32>,
+    pub school_year: Option<String>,
+    pub semester: Option<String>,
+    pub course: Option<String>,
+    pub year_level: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssessSchema {
+    // Defaults to every active student in the schedule's course and year level.
+    pub student_ids: Option<Vec<i32>>,
+}
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct Assessment {
+    pub id: i32,
+    pub student_id: i32,
+    pub payment_for: String,
+    pub amount: String,
+    pub fee_schedule_id: Option<i32>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    pub created_by: String,
+    pub academic_term_id: Option<i32>,
+    pub legacy_term: Option<String>,
+}
+
+// Limits a balance to one term's assessments and the payments recorded in it.
+#[derive(Debug, Deserialize)]
+pub struct BalanceQuery {
+    pub academic_term_id: Option<i32>,
+}
+
+// One row per `payment_for`, plus a totals row. Outstanding is negative when a
+// student has paid more than they were assessed.
+#[derive(Debug, FromRow, Serialize)]
+pub struct BalanceRow {
+    pub payment_for: Option<String>,
+    pub assessed: String,
+    pub paid: String,
+    pub outstanding: String,
+}
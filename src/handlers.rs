@@ -1,17 +1,20 @@
 use std::sync::Arc;
 use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use axum::{extract::State, http::{header, Response, StatusCode}, response::IntoResponse, Json, Extension};
+use axum::{extract::{Path, State}, http::{header, Response, StatusCode}, response::IntoResponse, Json, Extension};
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use jsonwebtoken::{encode, EncodingKey, Header};
 use rand_core::OsRng;
 use serde_json::json;
+use sqlx::PgConnection;
 
 use crate::{
     model::{LoginUserSchema, RegisterUserSchema, TokenClaims, User},
     response::FilteredUser,
     AppState,
 };
-use crate::model::ChangeRoleSchema;
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::model::{ChangeRoleSchema, ReplaceUserSchema, UpdateUserSchema};
+use crate::response::{db_error, not_found};
 
 pub async fn health_checker_handler() -> impl IntoResponse {
     const MESSAGE: &str = "RUST UCLM COOP API";
@@ -31,6 +34,7 @@ fn filter_user_record(user: &User) -> FilteredUser {
         username: user.username.to_owned(),
         name: user.name.to_owned(),
         photo: user.photo.clone(),
+        office: user.office.clone(),
         role: user.role.to_owned(),
         createdAt: user.created_at.unwrap(),
         updatedAt: user.updated_at.unwrap(),
@@ -39,6 +43,7 @@ fn filter_user_record(user: &User) -> FilteredUser {
 
 pub async fn register_user_handler(
     State(data): State<Arc<AppState>>,
+    ctx: AuditContext,
     Json(body): Json<RegisterUserSchema>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     let user_exists: Option<bool> =
@@ -87,6 +92,8 @@ pub async fn register_user_handler(
         })
         .map(|hash| hash.to_string())?;
 
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
     let user = sqlx::query_as!(
         User,
         "INSERT INTO \"users\" (name,email,password,username,role) VALUES ($1, $2, $3, $4, $5) RETURNING *",
@@ -96,7 +103,7 @@ pub async fn register_user_handler(
         body.username.to_string().to_ascii_lowercase(),
         body.role.unwrap_or_else(|| "non_admin".to_string()).to_ascii_lowercase()
     )
-        .fetch_one(&data.db)
+        .fetch_one(&mut *tx)
         .await
         .map_err(|e| {
             let error_response = json!({
@@ -106,6 +113,12 @@ pub async fn register_user_handler(
             (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
         })?;
 
+    log_event(&mut tx, &ctx, "create", "user", &user.id.to_string(), None, Some(snapshot(&filter_user_record(&user))))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
     let user_response = json!({"status": "success", "data": json!({
         "user": filter_user_record(&user)
     })});
@@ -113,6 +126,16 @@ pub async fn register_user_handler(
     Ok(Json(user_response))
 }
 
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(parsed_hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok(),
+
+        Err(_) => false,
+    }
+}
+
 pub async fn login_user_handler(
     State(data): State<Arc<AppState>>,
     Json(body): Json<LoginUserSchema>,
@@ -140,15 +163,7 @@ pub async fn login_user_handler(
             (StatusCode::BAD_REQUEST, Json(error_response))
         })?;
 
-    let is_valid = match PasswordHash::new(&user.password) {
-        Ok(parsed_hash) => Argon2::default()
-            .verify_password(body.password.as_bytes(), &parsed_hash)
-            .map_or(false ,|_| true),
-
-        Err(_) => false,
-    };
-
-    if !is_valid {
+    if !verify_password(&user.password, &body.password) {
         let error_response = json!({
             "status": "fail",
             "message": "Invalid email or password"
@@ -159,7 +174,7 @@ pub async fn login_user_handler(
 
     let now = chrono::Utc::now();
     let iat = now.timestamp() as usize;
-    let exp = (now + chrono::Duration::minutes(60)).timestamp() as usize;
+    let exp = (now + chrono::Duration::minutes(data.env.jwt_maxage as i64)).timestamp() as usize;
     let claims: TokenClaims = TokenClaims {
         sub: user.id.to_string(),
         exp,
@@ -175,7 +190,7 @@ pub async fn login_user_handler(
 
     let cookie = Cookie::build(("token", token.to_owned()))
         .path("/")
-        .max_age(time::Duration::hours(1))
+        .max_age(time::Duration::minutes(data.env.jwt_maxage as i64))
         .same_site(SameSite::Lax)
         .http_only(true);
 
@@ -245,12 +260,8 @@ pub async fn get_all_users_handler(
     Ok(Json(json_response))
 }
 
-pub async fn change_role_handler(
-    State(data): State<Arc<AppState>>,
-    Json(body): Json<ChangeRoleSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    // Validate role
-    if body.role != "admin" && body.role != "non_admin" {
+pub fn validate_role(role: &str) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if role != "admin" && role != "non_admin" {
         let error_response = json!({
             "status": "fail",
             "message": "Role must be either 'admin' or 'non_admin'",
@@ -258,34 +269,204 @@ pub async fn change_role_handler(
         return Err((StatusCode::BAD_REQUEST, Json(error_response)));
     }
 
+    Ok(())
+}
+
+async fn lock_user(
+    conn: &mut PgConnection,
+    id: uuid::Uuid,
+) -> Result<User, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as!(
+        User,
+        "SELECT * FROM \"users\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No user found with the provided ID"))
+}
+
+pub async fn change_role_handler(
+    State(data): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Json(body): Json<ChangeRoleSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    validate_role(&body.role)?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_user(&mut tx, body.id).await?;
+
     // Update user role in database
-    let updated_rows = sqlx::query!(
-        "UPDATE \"users\" SET role = $1 WHERE id = $2",
+    let user = sqlx::query_as!(
+        User,
+        "UPDATE \"users\" SET role = $1 WHERE id = $2 RETURNING *",
         body.role,
         body.id
     )
-        .execute(&data.db)
+        .fetch_one(&mut *tx)
         .await
-        .map_err(|e| {
-            let error_response = json!({
-            "status": "fail",
-            "message": format!("Database error: {}", e),
-        });
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-        })?;
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "change_role", "user", &user.id.to_string(), Some(snapshot(&filter_user_record(&before))), Some(snapshot(&filter_user_record(&user))))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let success_response = json!({
+        "status": "success",
+        "message": "User role updated successfully",
+    });
+
+    Ok(Json(success_response))
+}
+
+pub async fn get_user_handler(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let user = sqlx::query_as!(
+        User,
+        "SELECT * FROM \"users\" WHERE id = $1",
+        id
+    )
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No user found with the provided ID"))?;
 
-    if updated_rows.rows_affected() == 0 {
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "user": filter_user_record(&user)
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn update_user_handler(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<uuid::Uuid>,
+    ctx: AuditContext,
+    Json(body): Json<UpdateUserSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if let Some(role) = &body.role {
+        validate_role(role)?;
+    }
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_user(&mut tx, id).await?;
+
+    let user = sqlx::query_as!(
+        User,
+        "UPDATE \"users\" SET name = COALESCE($1, name), photo = COALESCE($2, photo), role = COALESCE($3, role), office = COALESCE($4, office), updated_at = NOW() WHERE id = $5 RETURNING *",
+        body.name,
+        body.photo,
+        body.role,
+        body.office,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "update", "user", &id.to_string(), Some(snapshot(&filter_user_record(&before))), Some(snapshot(&filter_user_record(&user))))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "user": filter_user_record(&user)
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn replace_user_handler(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<uuid::Uuid>,
+    ctx: AuditContext,
+    Json(body): Json<ReplaceUserSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    validate_role(&body.role)?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_user(&mut tx, id).await?;
+
+    let user = sqlx::query_as!(
+        User,
+        "UPDATE \"users\" SET name = $1, photo = $2, role = $3, office = $4, updated_at = NOW() WHERE id = $5 RETURNING *",
+        body.name,
+        body.photo,
+        body.role,
+        body.office,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "update", "user", &id.to_string(), Some(snapshot(&filter_user_record(&before))), Some(snapshot(&filter_user_record(&user))))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "user": filter_user_record(&user)
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn delete_user_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<uuid::Uuid>,
+    ctx: AuditContext,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if current_user.id == id {
         let error_response = json!({
             "status": "fail",
-            "message": "No user found with the provided ID",
+            "message": "You cannot delete your own account",
         });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
     }
 
-    let success_response = json!({
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_user(&mut tx, id).await?;
+
+    sqlx::query!(
+        "DELETE FROM \"users\" WHERE id = $1",
+        id
+    )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "delete", "user", &id.to_string(), Some(snapshot(&filter_user_record(&before))), None)
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let response = json!({
         "status": "success",
-        "message": "User role updated successfully",
+        "message": "User Successfully Deleted"
     });
 
-    Ok(Json(success_response))
-}
\ No newline at end of file
+    Ok(Json(response))
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+use axum::body::Body;
+use axum::http::{HeaderMap, Response, StatusCode};
+use axum::Json;
+use axum::response::IntoResponse;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use crate::model::User;
+use crate::response::{bad_request, conflict, db_error};
+
+pub const HEADER: &str = "Idempotency-Key";
+
+// Set on responses that were answered from a stored key rather than by doing the work.
+const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+// How often expired keys are removed.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// The key sent with the request, if any. Clients pick it; a UUID per submission is
+// what we suggest.
+pub fn key(headers: &HeaderMap) -> Result<Option<String>, (StatusCode, Json<Value>)> {
+    let Some(value) = headers.get(HEADER) else {
+        return Ok(None);
+    };
+    let key = value.to_str().unwrap_or_default().trim();
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return Err(bad_request(&format!("{} must be between 1 and {} characters", HEADER, MAX_KEY_LENGTH)));
+    }
+    Ok(Some(key.to_string()))
+}
+
+// Hashes the parsed body rather than the raw bytes, so a retry that formats the same
+// JSON differently still counts as the same request.
+pub fn request_hash<T: Serialize>(route: &str, body: &T) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(route.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(serde_json::to_vec(body).unwrap_or_default());
+    hex::encode(hasher.finalize())
+}
+
+pub struct Stored {
+    status_code: Option<i32>,
+    response: Option<Value>,
+}
+
+// Reserves the key for this request, or returns what was stored for it. Must run in
+// the transaction that does the work: a concurrent request with the same key waits on
+// the row until that transaction ends, and a failed request leaves no key behind, so
+// it can be retried.
+pub async fn claim(
+    conn: &mut PgConnection,
+    owner: &User,
+    key: &str,
+    request_hash: &str,
+    ttl_hours: i64,
+) -> Result<Option<Stored>, (StatusCode, Json<Value>)> {
+    sqlx::query!(
+        "DELETE FROM \"idempotency_keys\" WHERE owner_id = $1 AND key = $2 AND expires_at <= NOW()",
+        owner.id,
+        key
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    let claimed = sqlx::query_scalar!(
+        "INSERT INTO \"idempotency_keys\" (owner_id,key,request_hash,expires_at) VALUES ($1, $2, $3, NOW() + make_interval(hours => $4)) ON CONFLICT (owner_id, key) DO NOTHING RETURNING key",
+        owner.id,
+        key,
+        request_hash,
+        ttl_hours as i32
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    if claimed.is_some() {
+        return Ok(None);
+    }
+
+    let stored = sqlx::query!(
+        "SELECT request_hash, status_code, response FROM \"idempotency_keys\" WHERE owner_id = $1 AND key = $2",
+        owner.id,
+        key
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    if stored.request_hash != request_hash {
+        return Err(conflict(&format!("{} was already used for a different request", HEADER)));
+    }
+
+    Ok(Some(Stored {
+        status_code: stored.status_code,
+        response: stored.response,
+    }))
+}
+
+// Saves the response for a key claimed earlier in the same transaction.
+pub async fn store(
+    conn: &mut PgConnection,
+    owner: &User,
+    key: &str,
+    status: StatusCode,
+    response: &Value,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    sqlx::query!(
+        "UPDATE \"idempotency_keys\" SET status_code = $1, response = $2 WHERE owner_id = $3 AND key = $4",
+        status.as_u16() as i32,
+        response,
+        owner.id,
+        key
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    Ok(())
+}
+
+pub fn replay(stored: Stored) -> Result<Response<Body>, (StatusCode, Json<Value>)> {
+    let (Some(status_code), Some(response)) = (stored.status_code, stored.response) else {
+        return Err(conflict(&format!("A request with this {} is still being processed", HEADER)));
+    };
+    let status = u16::try_from(status_code)
+        .ok()
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .unwrap_or(StatusCode::OK);
+
+    Ok((status, [(REPLAYED_HEADER, "true")], Json(response)).into_response())
+}
+
+pub async fn cleanup(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM \"idempotency_keys\" WHERE expires_at <= NOW()")
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+pub fn spawn_cleanup(db: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = cleanup(&db).await {
+                println!("Idempotency key cleanup failed: {:?}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use axum::extract::{Multipart, Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use axum::response::IntoResponse;
+use serde_json::{json, Value};
+use sqlx::{Acquire, PgConnection};
+use crate::AppState;
+use crate::approvals::{self, ProposedChange};
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::catalogs;
+use crate::duplicates::{self, DuplicateCheck};
+use crate::import_model::{ImportBatch, ImportQuery, ImportRowError};
+use crate::imports;
+use crate::model::User;
+use crate::record_columns;
+use crate::record_handlers::{apply_create, check_payment_date, create_candidate};
+use crate::record_model::{CreateRecordSchema, Record};
+use crate::response::{bad_request, db_error};
+
+fn message(e: &(StatusCode, Json<Value>)) -> String {
+    e.1 .0
+        .get("message")
+        .and_then(Value::as_str)
+        .unwrap_or("Could not import this row")
+        .to_string()
+}
+
+// The checks `create_record_handler` makes, except that a row needing approval is an
+// error: a list is imported whole or not at all, so it cannot wait on a change request.
+async fn import_row(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    user: &User,
+    mut body: CreateRecordSchema,
+    duplicate_check: DuplicateCheck,
+) -> Result<Record, (StatusCode, Json<Value>)> {
+    check_payment_date(&body)?;
+    catalogs::normalize_create(&mut *conn, &mut body).await?;
+    duplicates::check(&mut *conn, duplicate_check, &create_candidate(&body)).await?;
+
+    let rules = approvals::matching_rules(&mut *conn, user, &ProposedChange::Create { created_at: body.created_at }).await?;
+    if !rules.is_empty() {
+        let names: Vec<&str> = rules.iter().map(|rule| rule.name.as_str()).collect();
+        return Err(bad_request(&format!(
+            "Needs approval under {}; enter this payment on its own",
+            names.join(", ")
+        )));
+    }
+
+    apply_create(conn, ctx, user, &body).await
+}
+
+// Takes a multipart upload with the list in a `file` field. Every row is validated and
+// applied inside one transaction, each under a savepoint so one bad row does not hide
+// the problems in the rest. Unless every row goes through, nothing is kept; with
+// `dry_run=true` nothing is kept either way.
+pub async fn import_records(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    ctx: AuditContext,
+    Query(params): Query<ImportQuery>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let dry_run = params.dry_run.unwrap_or(false);
+    let duplicate_check = DuplicateCheck::new(&data.env, params.confirm_duplicates.unwrap_or(false));
+
+    let mut upload = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| bad_request(&format!("Could not read the upload: {}", e)))?
+    {
+        if field.name() == Some("file") {
+            let filename = field.file_name().unwrap_or("upload").to_string();
+            let content_type = field.content_type().unwrap_or("").to_string();
+            let bytes = field
+                .bytes()
+                .await
+                .map_err(|e| bad_request(&format!("Could not read the upload: {}", e)))?;
+            upload = Some((filename, content_type, bytes));
+        }
+    }
+    let (filename, content_type, bytes) = upload.ok_or_else(|| bad_request("Attach the list as a file field named \"file\""))?;
+
+    let lower = filename.to_lowercase();
+    let format = if lower.ends_with(".xlsx") || content_type.contains("spreadsheetml") {
+        "xlsx"
+    } else if lower.ends_with(".csv") || content_type.starts_with("text/csv") {
+        "csv"
+    } else {
+        return Err(bad_request("Only .csv and .xlsx files can be imported"));
+    };
+    let sheet = match format {
+        "xlsx" => imports::parse_xlsx(&bytes)?,
+        _ => imports::parse_csv(&bytes, record_columns::delimiter(params.delimiter.as_deref())?)?,
+    };
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let batch = sqlx::query_as!(
+        ImportBatch,
+        "INSERT INTO \"import_batches\" (created_by_id,created_by,filename,format,row_count) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+        user.id,
+        user.username,
+        filename,
+        format,
+        sheet.rows.len() as i32
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    let mut errors: Vec<ImportRowError> = Vec::new();
+    for row in &sheet.rows {
+        let mut body = match row.to_schema(&user) {
+            Ok(body) => body,
+            Err(messages) => {
+                errors.push(ImportRowError { row: row.row, errors: messages });
+                continue;
+            }
+        };
+        body.import_batch_id = Some(batch.id);
+
+        let mut savepoint = tx.begin().await.map_err(db_error)?;
+        match import_row(&mut savepoint, &ctx, &user, body, duplicate_check).await {
+            Ok(_) => savepoint.commit().await.map_err(db_error)?,
+            Err(e) => {
+                savepoint.rollback().await.map_err(db_error)?;
+                errors.push(ImportRowError { row: row.row, errors: vec![message(&e)] });
+            }
+        }
+    }
+
+    let report = json!({
+        "dry_run": dry_run,
+        "filename": filename,
+        "format": format,
+        "total_rows": sheet.rows.len(),
+        "valid_rows": sheet.rows.len() - errors.len(),
+        "invalid_rows": errors.len(),
+        "ignored_columns": sheet.ignored_columns,
+        "errors": errors
+    });
+
+    if dry_run {
+        tx.rollback().await.map_err(db_error)?;
+        return Ok((StatusCode::OK, Json(json!({"status": "success", "data": report}))));
+    }
+    if !errors.is_empty() {
+        tx.rollback().await.map_err(db_error)?;
+        let error_response = json!({
+            "status": "fail",
+            "message": format!("{} of {} rows could not be imported; nothing was saved", errors.len(), sheet.rows.len()),
+            "data": report
+        });
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(error_response)));
+    }
+
+    log_event(&mut tx, &ctx, "create", "import_batch", &batch.id.to_string(), None, Some(snapshot(&batch)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "import_batch": batch,
+            "records_created": sheet.rows.len(),
+            "ignored_columns": sheet.ignored_columns
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
@@ -0,0 +1,34 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct ImportBatch {
+    pub id: uuid::Uuid,
+    pub created_by_id: uuid::Uuid,
+    pub created_by: String,
+    // Name of the uploaded file.
+    pub filename: String,
+    // "csv" or "xlsx".
+    pub format: String,
+    pub row_count: i32,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    // Validate every row and report, without creating anything.
+    pub dry_run: Option<bool>,
+    // CSV only; same values as the CSV export.
+    pub delimiter: Option<String>,
+    // Imports rows that look like payments already on file, or earlier in the same list.
+    pub confirm_duplicates: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportRowError {
+    // Spreadsheet row number, counting the header as row 1.
+    pub row: usize,
+    pub errors: Vec<String>,
+}
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use axum::http::StatusCode;
+use axum::Json;
+use calamine::{Data, Reader, Xlsx};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde_json::Value;
+use crate::amount;
+use crate::model::User;
+use crate::record_model::CreateRecordSchema;
+use crate::response::bad_request;
+
+// Uploads above this are refused before they are parsed.
+pub const MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
+
+// Columns an import understands, named after the fields of `CreateRecordSchema`. Headers
+// are matched loosely, so the headers of our own exports ("Payment For") work too.
+pub const IMPORT_FIELDS: [&str; 13] = [
+    "last_updated_by",
+    "first_name",
+    "last_name",
+    "mi",
+    "course",
+    "year_level",
+    "payment_for",
+    "amount",
+    "received_by",
+    "receipt_series_id",
+    "created_at",
+    "student_id",
+    "academic_term_id",
+];
+
+const REQUIRED_FIELDS: [&str; 5] = ["first_name", "last_name", "course", "year_level", "payment_for"];
+
+pub struct ImportRow {
+    // Spreadsheet row number, counting the header as row 1.
+    pub row: usize,
+    values: HashMap<&'static str, String>,
+}
+
+pub struct Sheet {
+    pub rows: Vec<ImportRow>,
+    // Headers that did not match any field and were left out.
+    pub ignored_columns: Vec<String>,
+}
+
+// "Payment For", "payment-for" and "PAYMENT_FOR" all name `payment_for`.
+fn field_for(header: &str) -> Option<&'static str> {
+    let key: String = header
+        .trim()
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    let key = match key.as_str() {
+        "middle_initial" => "mi",
+        other => other,
+    };
+    IMPORT_FIELDS.iter().find(|field| **field == key).copied()
+}
+
+// The "Total" row our own workbooks end with, so an export can be imported as it is: the
+// label in the first column, unless the amount is there, and nothing but the amount after
+// it. Only the last row is checked; anywhere else such a row is reported like any other.
+fn is_totals(columns: &[Option<&'static str>], cells: &[String]) -> bool {
+    let labelled = columns.first() == Some(&Some("amount"))
+        || cells.first().is_some_and(|label| label.trim().eq_ignore_ascii_case("total"));
+    labelled
+        && cells
+            .iter()
+            .zip(columns)
+            .skip(1)
+            .all(|(cell, field)| *field == Some("amount") || cell.trim().is_empty())
+}
+
+// The first row holds the headers; rows with nothing in them are skipped.
+fn sheet(table: Vec<Vec<String>>) -> Result<Sheet, (StatusCode, Json<Value>)> {
+    let mut table = table.into_iter();
+    let headers = table.next().ok_or_else(|| bad_request("The file is empty"))?;
+
+    let mut columns: Vec<Option<&'static str>> = Vec::new();
+    let mut ignored_columns = Vec::new();
+    for header in &headers {
+        let field = field_for(header);
+        if let Some(field) = field {
+            if columns.contains(&Some(field)) {
+                return Err(bad_request(&format!("Column {} appears more than once", field)));
+            }
+        } else if !header.trim().is_empty() {
+            ignored_columns.push(header.trim().to_string());
+        }
+        columns.push(field);
+    }
+    let missing: Vec<&str> = REQUIRED_FIELDS
+        .iter()
+        .filter(|field| !columns.contains(&Some(**field)))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(bad_request(&format!("Missing required columns: {}", missing.join(", "))));
+    }
+
+    let mut table: Vec<(usize, Vec<String>)> = table
+        .enumerate()
+        .filter(|(_, cells)| cells.iter().any(|cell| !cell.trim().is_empty()))
+        .collect();
+    if table.last().is_some_and(|(_, cells)| is_totals(&columns, cells)) {
+        table.pop();
+    }
+
+    let rows: Vec<ImportRow> = table
+        .into_iter()
+        .map(|(i, cells)| ImportRow {
+            row: i + 2,
+            values: columns
+                .iter()
+                .zip(cells)
+                .filter_map(|(field, cell)| field.map(|field| (field, cell.trim().to_string())))
+                .collect(),
+        })
+        .collect();
+    if rows.is_empty() {
+        return Err(bad_request("The file has no rows to import"));
+    }
+
+    Ok(Sheet { rows, ignored_columns })
+}
+
+pub fn parse_csv(bytes: &[u8], delimiter: char) -> Result<Sheet, (StatusCode, Json<Value>)> {
+    if !delimiter.is_ascii() {
+        return Err(bad_request("CSV imports need an ASCII delimiter"));
+    }
+    let bytes = bytes.strip_prefix("\u{feff}".as_bytes()).unwrap_or(bytes);
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter as u8)
+        .from_reader(bytes);
+
+    let mut table = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| bad_request(&format!("Could not read CSV row {}: {}", i + 1, e)))?;
+        table.push(record.iter().map(str::to_string).collect());
+    }
+    sheet(table)
+}
+
+// Dates come back as the date and time shown in the cell; whole numbers lose the ".0"
+// Excel stores them with.
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::String(value) | Data::DateTimeIso(value) | Data::DurationIso(value) => value.to_owned(),
+        Data::Int(value) => value.to_string(),
+        Data::Float(value) if value.fract() == 0.0 && value.abs() < 1e15 => (*value as i64).to_string(),
+        Data::Float(value) => value.to_string(),
+        Data::Bool(value) => value.to_string(),
+        Data::DateTime(value) => match value.as_datetime() {
+            Some(at) if at.time() == chrono::NaiveTime::MIN => at.format("%Y-%m-%d").to_string(),
+            Some(at) => at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => value.as_f64().to_string(),
+        },
+        Data::Error(e) => format!("#{}", e),
+    }
+}
+
+// Reads the first sheet of the workbook.
+pub fn parse_xlsx(bytes: &[u8]) -> Result<Sheet, (StatusCode, Json<Value>)> {
+    let mut workbook: Xlsx<_> = Xlsx::new(Cursor::new(bytes))
+        .map_err(|e| bad_request(&format!("Could not read the workbook: {}", e)))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| bad_request("The workbook has no sheets"))?
+        .map_err(|e| bad_request(&format!("Could not read the workbook: {}", e)))?;
+
+    let table = range
+        .rows()
+        .map(|cells| cells.iter().map(cell_text).collect())
+        .collect();
+    sheet(table)
+}
+
+// Timestamps without an offset are taken as local time, like the dates in our exports.
+fn parse_datetime(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Some(at.with_timezone(&Utc));
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            ["%Y-%m-%d", "%m/%d/%Y"]
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+                .map(|date| date.and_time(chrono::NaiveTime::MIN))
+        })?;
+    Local.from_local_datetime(&naive).earliest().map(|at| at.with_timezone(&Utc))
+}
+
+impl ImportRow {
+    fn text(&self, field: &str) -> String {
+        self.values.get(field).cloned().unwrap_or_default()
+    }
+
+    fn id(&self, field: &str, errors: &mut Vec<String>) -> Option<i32> {
+        let value = self.text(field);
+        if value.is_empty() {
+            return None;
+        }
+        value
+            .parse()
+            .map_err(|_| errors.push(format!("{} must be a whole number, got {:?}", field, value)))
+            .ok()
+    }
+
+    // Checks what can be checked without the database. Catalog names, receipts, students
+    // and terms are checked when the row is applied.
+    pub fn to_schema(&self, user: &User) -> Result<CreateRecordSchema, Vec<String>> {
+        let mut errors = Vec::new();
+        for field in REQUIRED_FIELDS {
+            if self.text(field).is_empty() {
+                errors.push(format!("{} is required", field));
+            }
+        }
+
+        let amount = self.text("amount");
+        if !amount.is_empty() && !matches!(amount::parse_cents(&amount), Some(cents) if cents > 0) {
+            errors.push(format!("amount must be a positive number, got {:?}", amount));
+        }
+
+        let created_at = self.text("created_at");
+        let created_at = if created_at.is_empty() {
+            None
+        } else {
+            let parsed = parse_datetime(&created_at);
+            if parsed.is_none() {
+                errors.push(format!("created_at is not a date we understand: {:?}", created_at));
+            }
+            parsed
+        };
+
+        let receipt_series_id = self.id("receipt_series_id", &mut errors);
+        let student_id = self.id("student_id", &mut errors);
+        let academic_term_id = self.id("academic_term_id", &mut errors);
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let or_user = |field: &str| {
+            let value = self.text(field);
+            if value.is_empty() { user.username.to_owned() } else { value }
+        };
+        Ok(CreateRecordSchema {
+            last_updated_by: or_user("last_updated_by"),
+            first_name: self.text("first_name"),
+            last_name: self.text("last_name"),
+            mi: self.text("mi"),
+            course: self.text("course"),
+            year_level: self.text("year_level"),
+            payment_for: self.text("payment_for"),
+            amount,
+            received_by: or_user("received_by"),
+            receipt_series_id,
+            created_at,
+            student_id,
+            academic_term_id,
+            import_batch_id: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+    fn local(date: &str) -> Option<DateTime<Utc>> {
+        let naive = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").ok()?;
+        Local.from_local_datetime(&naive).earliest().map(|at| at.with_timezone(&Utc))
+    }
+
+    fn test_user() -> User {
+        User {
+            id: uuid::Uuid::nil(),
+            name: "Cashier".to_string(),
+            email: "cashier@example.com".to_string(),
+            username: "cashier".to_string(),
+            password: String::new(),
+            role: "user".to_string(),
+            photo: None,
+            created_at: None,
+            updated_at: None,
+            office: None,
+        }
+    }
+
+    fn csv(text: &str) -> Sheet {
+        parse_csv(text.as_bytes(), ',').unwrap()
+    }
+
+    #[test]
+    fn matches_headers_loosely() {
+        assert_eq!(field_for("Payment For"), Some("payment_for"));
+        assert_eq!(field_for(" payment-for "), Some("payment_for"));
+        assert_eq!(field_for("PAYMENT_FOR"), Some("payment_for"));
+        assert_eq!(field_for("Middle Initial"), Some("mi"));
+        assert_eq!(field_for("Amount"), Some("amount"));
+        assert_eq!(field_for("ID"), None);
+        assert_eq!(field_for("OR Number"), None);
+        assert_eq!(field_for(""), None);
+    }
+
+    #[test]
+    fn parses_timestamps_with_and_without_an_offset() {
+        assert_eq!(parse_datetime("2026-10-19T08:30:00+08:00"), Some(Utc.with_ymd_and_hms(2026, 10, 19, 0, 30, 0).unwrap()));
+        assert_eq!(parse_datetime("2026-10-19T00:30:00Z"), Some(Utc.with_ymd_and_hms(2026, 10, 19, 0, 30, 0).unwrap()));
+        assert_eq!(parse_datetime("2026-10-19 08:30:15"), local("2026-10-19 08:30:15"));
+        assert_eq!(parse_datetime("2026-10-19 08:30"), local("2026-10-19 08:30:00"));
+        assert_eq!(parse_datetime("2026-10-19T08:30:15"), local("2026-10-19 08:30:15"));
+        assert_eq!(parse_datetime("2026-10-19"), local("2026-10-19 00:00:00"));
+        assert_eq!(parse_datetime("10/19/2026"), local("2026-10-19 00:00:00"));
+        assert_eq!(parse_datetime("19/10/2026"), None);
+        assert_eq!(parse_datetime("yesterday"), None);
+    }
+
+    #[test]
+    fn renders_cells_as_they_are_shown() {
+        assert_eq!(cell_text(&Data::Empty), "");
+        assert_eq!(cell_text(&Data::String("Juan".to_string())), "Juan");
+        assert_eq!(cell_text(&Data::Int(42)), "42");
+        assert_eq!(cell_text(&Data::Float(1500.0)), "1500");
+        assert_eq!(cell_text(&Data::Float(1500.5)), "1500.5");
+        assert_eq!(cell_text(&Data::Bool(true)), "true");
+
+        // 46314 is 2026-10-19 in Excel's 1900 date system; .5 is noon.
+        let date = ExcelDateTime::new(46314.0, ExcelDateTimeType::DateTime, false);
+        assert_eq!(cell_text(&Data::DateTime(date)), "2026-10-19");
+        let noon = ExcelDateTime::new(46314.5, ExcelDateTimeType::DateTime, false);
+        assert_eq!(cell_text(&Data::DateTime(noon)), "2026-10-19 12:00:00");
+    }
+
+    #[test]
+    fn strips_a_byte_order_mark_from_csv() {
+        let sheet = csv("\u{feff}First Name,Last Name,Course,Year Level,Payment For\nJuan,Cruz,BSIT,1,Tuition\n");
+        assert_eq!(sheet.rows.len(), 1);
+        assert_eq!(sheet.rows[0].text("first_name"), "Juan");
+        assert!(sheet.ignored_columns.is_empty());
+    }
+
+    #[test]
+    fn drops_only_a_trailing_total_row() {
+        let header = "ID,First Name,Last Name,Course,Year Level,Payment For,Amount\n";
+        let sheet = csv(&format!("{}1,Juan,Cruz,BSIT,1,Tuition,1500\nTotal,,,,,,1500\n", header));
+        assert_eq!(sheet.rows.len(), 1);
+        assert_eq!(sheet.ignored_columns, vec!["ID"]);
+
+        // A payment with only its amount keyed in is reported, not taken for a total.
+        let sheet = csv(&format!("{}1,Juan,Cruz,BSIT,1,Tuition,1500\n,,,,,,250\n", header));
+        assert_eq!(sheet.rows.len(), 2);
+        assert_eq!(sheet.rows[1].row, 3);
+        assert!(sheet.rows[1].to_schema(&test_user()).is_err());
+
+        let sheet = csv(&format!("{}Total,,,,,,1500\n1,Juan,Cruz,BSIT,1,Tuition,1500\n", header));
+        assert_eq!(sheet.rows.len(), 2);
+    }
+
+    #[test]
+    fn drops_a_trailing_total_without_a_label_when_amount_comes_first() {
+        let sheet = csv("Amount,First Name,Last Name,Course,Year Level,Payment For\n1500,Juan,Cruz,BSIT,1,Tuition\n1500,,,,,\n");
+        assert_eq!(sheet.rows.len(), 1);
+    }
+}
@@ -0,0 +1,346 @@
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::{Months, NaiveDate};
+use serde_json::Value;
+use sqlx::PgConnection;
+use crate::amount;
+use crate::plan_model::{InstallmentStatus, PaymentPlan, PaymentPlanWithInstallments, PlanInstallment};
+use crate::record_model::Record;
+use crate::response::db_error;
+
+pub const AGING_BUCKETS: [&str; 4] = ["1-30", "31-60", "61-90", "90+"];
+
+// Splits `total` cents into `count` installments; the last one absorbs the remainder.
+pub fn split(total: i64, count: i64) -> Vec<i64> {
+    let each = total / count;
+    let mut parts = vec![each; count as usize];
+    if let Some(last) = parts.last_mut() {
+        *last += total - each * count;
+    }
+    parts
+}
+
+// Falls back to the month's last day, so a plan starting on the 31st stays at month end.
+pub fn add_months(date: NaiveDate, months: u32) -> Option<NaiveDate> {
+    date.checked_add_months(Months::new(months))
+}
+
+// One payment's share of one installment.
+#[derive(Debug, PartialEq)]
+struct Allocation {
+    installment_id: i32,
+    record_id: i32,
+    cents: i64,
+}
+
+// Applies `records` (oldest first) to `installments` (in the order they are paid off)
+// and returns the allocations along with what is still owed on each installment. A
+// payment counts for what is left of it after its voids and refunds, and only toward
+// plans that had started by the day it was made.
+fn allocate(plans: &[PaymentPlan], installments: &[PlanInstallment], records: &[Record]) -> (Vec<Allocation>, Vec<i64>) {
+    let mut remaining: Vec<i64> = installments
+        .iter()
+        .map(|installment| amount::parse_cents(&installment.amount).unwrap_or(0))
+        .collect();
+    let mut allocations = Vec::new();
+
+    for payment in records.iter().filter(|record| record.entry_type == "payment") {
+        let mut left = amount::parse_cents(&payment.amount).unwrap_or(0)
+            + records
+                .iter()
+                .filter(|record| record.reverses_record_id == Some(payment.id))
+                .filter_map(|record| amount::parse_cents(&record.amount))
+                .sum::<i64>();
+        let paid_on = payment.created_at.map(|created_at| created_at.date_naive());
+
+        for (index, installment) in installments.iter().enumerate() {
+            if left <= 0 {
+                break;
+            }
+            let plan = plans.iter().find(|plan| plan.id == installment.plan_id);
+            if plan.zip(paid_on).is_some_and(|(plan, paid_on)| paid_on < plan.start_date) {
+                continue;
+            }
+            let applied = left.min(remaining[index]);
+            if applied <= 0 {
+                continue;
+            }
+            allocations.push(Allocation {
+                installment_id: installment.id,
+                record_id: payment.id,
+                cents: applied,
+            });
+            remaining[index] -= applied;
+            left -= applied;
+        }
+    }
+
+    (allocations, remaining)
+}
+
+// Reapplies every payment a student made toward `payment_for` to their plans for it,
+// oldest payment to earliest installment first. Runs in the caller's transaction after
+// any change to those payments, so allocations never drift from the records.
+pub async fn rebuild(
+    conn: &mut PgConnection,
+    student_id: i32,
+    payment_for: &str,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let plans = sqlx::query_as!(
+        PaymentPlan,
+        "SELECT * FROM \"payment_plans\" WHERE student_id = $1 AND payment_for = $2 AND status <> 'cancelled' ORDER BY start_date, id FOR UPDATE",
+        student_id,
+        payment_for
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    if plans.is_empty() {
+        return Ok(());
+    }
+    let plan_ids: Vec<i32> = plans.iter().map(|plan| plan.id).collect();
+
+    sqlx::query!(
+        "DELETE FROM \"installment_allocations\" a USING \"plan_installments\" i WHERE a.installment_id = i.id AND i.plan_id = ANY($1)",
+        &plan_ids
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    let mut installments = sqlx::query_as!(
+        PlanInstallment,
+        "SELECT * FROM \"plan_installments\" WHERE plan_id = ANY($1) ORDER BY due_date, sequence",
+        &plan_ids
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    // Earlier plans are paid off before later ones.
+    installments.sort_by_key(|installment| plan_ids.iter().position(|id| *id == installment.plan_id));
+
+    let records = sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE student_id = $1 AND payment_for = $2 AND deleted_at IS NULL ORDER BY created_at, id",
+        student_id,
+        payment_for
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    let (allocations, remaining) = allocate(&plans, &installments, &records);
+    for allocation in &allocations {
+        sqlx::query!(
+            "INSERT INTO \"installment_allocations\" (installment_id,record_id,amount) VALUES ($1, $2, $3)",
+            allocation.installment_id,
+            allocation.record_id,
+            amount::format_cents(allocation.cents)
+        )
+            .execute(&mut *conn)
+            .await
+            .map_err(db_error)?;
+    }
+
+    for plan in &plans {
+        let paid_off = installments
+            .iter()
+            .zip(&remaining)
+            .filter(|(installment, _)| installment.plan_id == plan.id)
+            .all(|(_, remaining)| *remaining <= 0);
+        let status = if paid_off { "completed" } else { "active" };
+        if plan.status != status {
+            sqlx::query!(
+                "UPDATE \"payment_plans\" SET status = $1, updated_at = NOW() WHERE id = $2",
+                status,
+                plan.id
+            )
+                .execute(&mut *conn)
+                .await
+                .map_err(db_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Rebuilds the plans a record's payment counts toward, if it belongs to a student.
+pub async fn rebuild_for(
+    conn: &mut PgConnection,
+    record: &Record,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    match record.student_id {
+        Some(student_id) => rebuild(conn, student_id, &record.payment_for).await,
+        None => Ok(()),
+    }
+}
+
+pub async fn with_installments(
+    conn: &mut PgConnection,
+    plans: Vec<PaymentPlan>,
+) -> Result<Vec<PaymentPlanWithInstallments>, (StatusCode, Json<Value>)> {
+    let plan_ids: Vec<i32> = plans.iter().map(|plan| plan.id).collect();
+    let installments: Vec<InstallmentStatus> = sqlx::query_as::<_, InstallmentStatus>(
+        "SELECT i.id, i.plan_id, i.sequence, i.due_date, i.amount,
+            COALESCE(SUM(record_amount(a.amount)), 0)::NUMERIC(14, 2)::TEXT AS paid,
+            (record_amount(i.amount) - COALESCE(SUM(record_amount(a.amount)), 0))::NUMERIC(14, 2)::TEXT AS outstanding
+        FROM \"plan_installments\" i
+        LEFT JOIN \"installment_allocations\" a ON a.installment_id = i.id
+        WHERE i.plan_id = ANY($1)
+        GROUP BY i.id
+        ORDER BY i.plan_id, i.due_date, i.sequence"
+    )
+        .bind(&plan_ids)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    Ok(plans
+        .into_iter()
+        .map(|plan| PaymentPlanWithInstallments {
+            installments: installments.iter().filter(|installment| installment.plan_id == plan.id).cloned().collect(),
+            plan,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn plan(id: i32, start_date: NaiveDate) -> PaymentPlan {
+        serde_json::from_value(json!({
+            "id": id,
+            "student_id": 1,
+            "payment_for": "Tuition",
+            "total_amount": "0.00",
+            "start_date": start_date,
+            "status": "active",
+            "created_by": "admin"
+        }))
+        .unwrap()
+    }
+
+    fn installment(id: i32, plan_id: i32, amount: &str) -> PlanInstallment {
+        PlanInstallment {
+            id,
+            plan_id,
+            sequence: id,
+            due_date: date(2026, 1, 1),
+            amount: amount.to_string(),
+        }
+    }
+
+    fn record(id: i32, entry_type: &str, reverses: Option<i32>, amount: &str, paid_on: NaiveDate) -> Record {
+        serde_json::from_value(json!({
+            "id": id,
+            "createdAt": paid_on.and_hms_opt(9, 0, 0).unwrap().and_utc(),
+            "last_updated_by": "cashier",
+            "first_name": "Juan",
+            "last_name": "Cruz",
+            "mi": "D",
+            "course": "BSIT",
+            "year_level": "1",
+            "payment_for": "Tuition",
+            "amount": amount,
+            "received_by": "cashier",
+            "version": 1,
+            "entry_type": entry_type,
+            "reverses_record_id": reverses
+        }))
+        .unwrap()
+    }
+
+    fn allocation(installment_id: i32, record_id: i32, cents: i64) -> Allocation {
+        Allocation { installment_id, record_id, cents }
+    }
+
+    #[test]
+    fn split_puts_the_remainder_on_the_last_installment() {
+        assert_eq!(split(100000, 3), vec![33333, 33333, 33334]);
+        assert_eq!(split(90000, 3), vec![30000, 30000, 30000]);
+        assert_eq!(split(2, 3), vec![0, 0, 2]);
+        assert_eq!(split(150000, 1), vec![150000]);
+    }
+
+    #[test]
+    fn split_parts_add_up_to_the_total() {
+        for (total, count) in [(1, 7), (1234567, 12), (9999, 4)] {
+            assert_eq!(split(total, count).iter().sum::<i64>(), total);
+        }
+    }
+
+    #[test]
+    fn add_months_stays_at_month_end() {
+        let start = date(2026, 1, 31);
+        assert_eq!(add_months(start, 0), Some(start));
+        assert_eq!(add_months(start, 1), Some(date(2026, 2, 28)));
+        assert_eq!(add_months(start, 2), Some(date(2026, 3, 31)));
+        assert_eq!(add_months(start, 3), Some(date(2026, 4, 30)));
+        assert_eq!(add_months(date(2027, 12, 31), 2), Some(date(2028, 2, 29)));
+        assert_eq!(add_months(date(2026, 11, 15), 3), Some(date(2027, 2, 15)));
+    }
+
+    #[test]
+    fn payments_fill_installments_in_order() {
+        let plans = [plan(1, date(2026, 1, 1))];
+        let installments = [installment(10, 1, "500.00"), installment(11, 1, "500.00")];
+        let records = [
+            record(1, "payment", None, "300.00", date(2026, 1, 5)),
+            record(2, "payment", None, "400.00", date(2026, 2, 5)),
+        ];
+
+        let (allocations, remaining) = allocate(&plans, &installments, &records);
+        assert_eq!(
+            allocations,
+            vec![allocation(10, 1, 30000), allocation(10, 2, 20000), allocation(11, 2, 20000)]
+        );
+        assert_eq!(remaining, vec![0, 30000]);
+    }
+
+    #[test]
+    fn overpayment_is_left_unallocated() {
+        let plans = [plan(1, date(2026, 1, 1))];
+        let installments = [installment(10, 1, "100.00")];
+        let records = [record(1, "payment", None, "250.00", date(2026, 1, 5))];
+
+        let (allocations, remaining) = allocate(&plans, &installments, &records);
+        assert_eq!(allocations, vec![allocation(10, 1, 10000)]);
+        assert_eq!(remaining, vec![0]);
+    }
+
+    #[test]
+    fn reversed_payments_only_count_what_is_left() {
+        let plans = [plan(1, date(2026, 1, 1))];
+        let installments = [installment(10, 1, "500.00"), installment(11, 1, "500.00")];
+        let records = [
+            record(1, "payment", None, "600.00", date(2026, 1, 5)),
+            record(2, "payment", None, "200.00", date(2026, 1, 6)),
+            record(3, "refund", Some(1), "-150.00", date(2026, 1, 7)),
+            record(4, "void", Some(2), "-200.00", date(2026, 1, 8)),
+        ];
+
+        let (allocations, remaining) = allocate(&plans, &installments, &records);
+        assert_eq!(allocations, vec![allocation(10, 1, 45000)]);
+        assert_eq!(remaining, vec![5000, 50000]);
+    }
+
+    #[test]
+    fn payments_before_a_plan_starts_are_not_applied_to_it() {
+        let plans = [plan(1, date(2026, 1, 1)), plan(2, date(2026, 6, 1))];
+        let installments = [installment(10, 1, "100.00"), installment(20, 2, "100.00")];
+        let records = [
+            record(1, "payment", None, "150.00", date(2026, 3, 1)),
+            record(2, "payment", None, "80.00", date(2026, 6, 1)),
+        ];
+
+        let (allocations, remaining) = allocate(&plans, &installments, &records);
+        assert_eq!(allocations, vec![allocation(10, 1, 10000), allocation(20, 2, 8000)]);
+        assert_eq!(remaining, vec![0, 2000]);
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use futures_util::TryStreamExt;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgConnection, PgPool};
+use crate::audit::snapshot;
+use crate::record_model::Record;
+
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Arbitrary key for the advisory lock that serialises appends to the chain.
+const LEDGER_LOCK_KEY: i64 = 0x5245_434f_5244;
+
+#[derive(Debug, FromRow)]
+struct LedgerEntry {
+    seq: i64,
+    record_id: i32,
+    payload: String,
+    prev_hash: String,
+    hash: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BrokenLink {
+    pub seq: i64,
+    pub record_id: i32,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordMismatch {
+    pub record_id: i32,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LedgerReport {
+    pub valid: bool,
+    pub entries_checked: u64,
+    pub head_hash: String,
+    pub first_broken_link: Option<BrokenLink>,
+    pub mismatched_records: Vec<RecordMismatch>,
+    // Records written before the ledger existed have no entry to check against.
+    pub untracked_records: Vec<i32>,
+}
+
+fn chain_hash(prev_hash: &str, payload: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(prev_hash.as_bytes());
+    hasher.update(payload.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+// Serialises with object keys sorted at every level, independently of how
+// `serde_json::Map` orders them (its `preserve_order` feature can be switched on by
+// any crate in the build), so stored hashes stay reproducible.
+fn canonical(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|key| format!("{}:{}", Value::String(key.to_owned()), canonical(&map[key])))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+fn entry_payload(action: &str, record_id: i32, record: Option<Value>) -> String {
+    canonical(&json!({
+        "action": action,
+        "record_id": record_id,
+        "record": record
+    }))
+}
+
+// Appends the state of a record after a mutation (`None` once it has been purged).
+// Must run inside the mutation's transaction; the advisory lock is held until it ends.
+pub async fn append(
+    conn: &mut PgConnection,
+    action: &str,
+    record_id: i32,
+    record: Option<&Record>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(LEDGER_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    let prev_hash = sqlx::query_scalar!(
+        "SELECT hash FROM \"record_ledger\" ORDER BY seq DESC LIMIT 1"
+    )
+        .fetch_optional(&mut *conn)
+        .await?
+        .unwrap_or_else(|| GENESIS_HASH.to_string());
+
+    let payload = entry_payload(action, record_id, record.map(snapshot));
+    let hash = chain_hash(&prev_hash, &payload);
+
+    sqlx::query!(
+        "INSERT INTO \"record_ledger\" (record_id,action,payload,prev_hash,hash) VALUES ($1, $2, $3, $4, $5)",
+        record_id,
+        action,
+        payload,
+        prev_hash,
+        hash
+    )
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+// Columns added to `records` after an entry was written are not part of its payload,
+// so only the fields the entry knows about are compared.
+fn matches_snapshot(expected: &Value, actual: &Value) -> bool {
+    match (expected.as_object(), actual.as_object()) {
+        (Some(expected), Some(actual)) => expected
+            .iter()
+            .all(|(key, value)| actual.get(key).unwrap_or(&Value::Null) == value),
+        _ => expected == actual,
+    }
+}
+
+// Walks the whole chain from the genesis hash, stopping at the first entry whose
+// link or hash does not check out, then compares the latest ledger state of every
+// record with what is actually in the `records` table.
+pub async fn verify(db: &PgPool) -> Result<LedgerReport, sqlx::Error> {
+    let mut expected_prev = GENESIS_HASH.to_string();
+    let mut entries_checked = 0;
+    let mut first_broken_link = None;
+    let mut latest: HashMap<i32, Value> = HashMap::new();
+
+    let mut entries = sqlx::query_as::<_, LedgerEntry>(
+        "SELECT seq, record_id, payload, prev_hash, hash FROM \"record_ledger\" ORDER BY seq"
+    )
+        .fetch(db);
+
+    while let Some(entry) = entries.try_next().await? {
+        entries_checked += 1;
+
+        let reason = if entry.prev_hash != expected_prev {
+            Some("previous hash does not match the preceding entry".to_string())
+        } else if chain_hash(&entry.prev_hash, &entry.payload) != entry.hash {
+            Some("entry hash does not match its payload".to_string())
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            first_broken_link = Some(BrokenLink {
+                seq: entry.seq,
+                record_id: entry.record_id,
+                reason,
+            });
+            break;
+        }
+
+        let payload: Value = serde_json::from_str(&entry.payload).unwrap_or(Value::Null);
+        latest.insert(entry.record_id, payload.get("record").cloned().unwrap_or(Value::Null));
+        expected_prev = entry.hash;
+    }
+    drop(entries);
+
+    let mut mismatched_records = Vec::new();
+    let mut untracked_records = Vec::new();
+
+    if first_broken_link.is_none() {
+        let records = sqlx::query_as!(
+            Record,
+            "SELECT * FROM \"records\" ORDER BY id"
+        )
+            .fetch_all(db)
+            .await?;
+
+        for record in &records {
+            match latest.remove(&record.id) {
+                None => untracked_records.push(record.id),
+                Some(Value::Null) => mismatched_records.push(RecordMismatch {
+                    record_id: record.id,
+                    reason: "record was purged according to the ledger but still exists".to_string(),
+                }),
+                Some(expected) if !matches_snapshot(&expected, &snapshot(record)) => mismatched_records.push(RecordMismatch {
+                    record_id: record.id,
+                    reason: "record differs from its last ledger entry".to_string(),
+                }),
+                Some(_) => {}
+            }
+        }
+
+        let mut removed: Vec<i32> = latest
+            .into_iter()
+            .filter(|(_, state)| !state.is_null())
+            .map(|(record_id, _)| record_id)
+            .collect();
+        removed.sort_unstable();
+        mismatched_records.extend(removed.into_iter().map(|record_id| RecordMismatch {
+            record_id,
+            reason: "record is missing but was never purged through the API".to_string(),
+        }));
+    }
+
+    Ok(LedgerReport {
+        valid: first_broken_link.is_none() && mismatched_records.is_empty(),
+        entries_checked,
+        head_hash: expected_prev,
+        first_broken_link,
+        mismatched_records,
+        untracked_records,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_keys_are_sorted_at_every_level() {
+        let record = json!({
+            "zeta": 1,
+            "amount": "150.00",
+            "nested": {"b": [{"y": 1, "x": 2}], "a": null},
+            "name": "Juan Dela Cruz"
+        });
+
+        assert_eq!(
+            entry_payload("create", 42, Some(record)),
+            r#"{"action":"create","record":{"amount":"150.00","name":"Juan Dela Cruz","nested":{"a":null,"b":[{"x":2,"y":1}]},"zeta":1},"record_id":42}"#
+        );
+    }
+
+    #[test]
+    fn known_entry_hash_is_stable() {
+        let record = json!({
+            "zeta": 1,
+            "amount": "150.00",
+            "nested": {"b": [{"y": 1, "x": 2}], "a": null},
+            "name": "Juan Dela Cruz"
+        });
+        let payload = entry_payload("create", 42, Some(record));
+
+        assert_eq!(
+            chain_hash(GENESIS_HASH, &payload),
+            "2b162ae64c2ca468e4e9064e4790dc1f1ca71cb1f536500b49859e0c3aa75390"
+        );
+    }
+
+    #[test]
+    fn purged_record_is_null() {
+        assert_eq!(
+            entry_payload("purge", 7, None),
+            r#"{"action":"purge","record":null,"record_id":7}"#
+        );
+    }
+}
@@ -1,15 +1,58 @@
 mod handlers;
+mod amount;
+mod approval_handlers;
+mod approval_model;
+mod approvals;
+mod audit;
+mod audit_handlers;
+mod audit_model;
+mod batch_handlers;
+mod catalog_handlers;
+mod catalog_model;
+mod catalogs;
 mod routes;
 mod config;
+mod duplicates;
+mod etag;
+mod export_handlers;
+mod export_model;
+mod exports;
+mod fee_handlers;
+mod fee_model;
 mod model;
+mod plan_handlers;
+mod plan_model;
+mod receipt_handlers;
+mod receipt_model;
+mod receipt_pdf;
+mod receipt_token;
+mod receipts;
+mod report_handlers;
+mod report_model;
+mod reversal_handlers;
 mod response;
+mod idempotency;
+mod import_handlers;
+mod import_model;
+mod imports;
+mod installments;
 mod jwt_auth;
+mod ledger;
+mod record_columns;
 mod record_handlers;
 mod record_model;
+mod student_handlers;
+mod student_model;
+mod students;
+mod term_handlers;
+mod term_model;
+mod terms;
+mod xlsx;
 
+use std::net::SocketAddr;
 use std::sync::Arc;
-use axum::http::{HeaderValue, Method};
-use axum::http::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use axum::http::{HeaderName, HeaderValue, Method};
+use axum::http::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MATCH};
 use sqlx::{Pool, Postgres};
 use sqlx::postgres::PgPoolOptions;
 use tower_http::cors::CorsLayer;
@@ -43,13 +86,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>>{
         }
     };
 
-    println!("{}", format!("starting server at port {}", config.port));
+    // `rust-api verify-ledger` checks the record hash chain and exits instead of serving.
+    if std::env::args().nth(1).as_deref() == Some("verify-ledger") {
+        let report = ledger::verify(&pool).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        std::process::exit(if report.valid { 0 } else { 1 });
+    }
+
+    println!("starting server at port {}", config.port);
+
+    exports::spawn_cleanup(pool.clone());
+    idempotency::spawn_cleanup(pool.clone());
 
     let cors = CorsLayer::new()
         .allow_origin("http://localhost:3000".parse::<HeaderValue>().unwrap())
         .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE, Method::PUT])
         .allow_credentials(true)
-        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE]);
+        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE, IF_MATCH, HeaderName::from_static("x-request-id")])
+        .expose_headers([ETAG]);
 
     let app = create_router(Arc::new(AppState {
         db: pool.clone(),
@@ -61,7 +115,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>>{
 
     println!("🚀 Server started at http://localhost:{}", config.port);
 
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 
@@ -16,6 +16,7 @@ pub struct User {
     pub created_at: Option<DateTime<Utc>>,
     #[serde(rename = "updatedAt")]
     pub updated_at: Option<DateTime<Utc>>,
+    pub office: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,6 +41,24 @@ pub struct LoginUserSchema {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserSchema {
+    pub name: Option<String>,
+    pub photo: Option<String>,
+    pub role: Option<String>,
+    pub office: Option<String>
+}
+
+// Body of `PUT /api/users/:id`; optional fields left out are cleared.
+#[derive(Debug, Deserialize)]
+pub struct ReplaceUserSchema {
+    pub name: String,
+    pub photo: Option<String>,
+    pub role: String,
+    pub office: Option<String>
+}
+
+// Body of the deprecated `PUT /api/users/change_role`.
 #[derive(Debug, Deserialize)]
 pub struct ChangeRoleSchema {
     pub id: uuid::Uuid,
@@ -0,0 +1,306 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use axum::response::IntoResponse;
+use chrono::Utc;
+use serde_json::json;
+use sqlx::PgConnection;
+use crate::AppState;
+use crate::amount;
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::catalogs;
+use crate::installments::{self, add_months, split};
+use crate::model::User;
+use crate::plan_model::{CancelPaymentPlanSchema, CreatePaymentPlanSchema, PaymentPlan};
+use crate::response::{bad_request, conflict, db_error, not_found};
+use crate::student_model::Student;
+
+// Assessed minus paid for one category, in cents.
+async fn outstanding_cents(
+    conn: &mut PgConnection,
+    student_id: i32,
+    payment_for: &str,
+) -> Result<i64, (StatusCode, Json<serde_json::Value>)> {
+    let outstanding = sqlx::query_scalar!(
+        "SELECT (COALESCE((SELECT SUM(record_amount(amount)) FROM \"assessments\" WHERE student_id = $1 AND payment_for = $2), 0)
+            - COALESCE((SELECT SUM(record_amount(amount)) FROM \"records\" WHERE student_id = $1 AND payment_for = $2 AND deleted_at IS NULL), 0))::NUMERIC(14, 2)::TEXT",
+        student_id,
+        payment_for
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    Ok(outstanding.as_deref().and_then(amount::parse_cents).unwrap_or(0))
+}
+
+fn positive_cents(field: &str, value: &str) -> Result<i64, (StatusCode, Json<serde_json::Value>)> {
+    amount::parse_cents(value)
+        .filter(|cents| *cents > 0)
+        .ok_or_else(|| bad_request(&format!("{} must be a positive number", field)))
+}
+
+// Due dates and amounts in cents, in due date order.
+type Schedule = Vec<(chrono::NaiveDate, i64)>;
+
+fn schedule(
+    body: &CreatePaymentPlanSchema,
+    total: Option<i64>,
+) -> Result<Schedule, (StatusCode, Json<serde_json::Value>)> {
+    let mut installments = match (&body.installments, body.count, body.first_due_date) {
+        (Some(installments), None, None) => {
+            let mut listed = Vec::with_capacity(installments.len());
+            for installment in installments {
+                listed.push((installment.due_date, positive_cents("Installment amount", &installment.amount)?));
+            }
+            if let Some(total) = total {
+                let sum: i64 = listed.iter().map(|(_, cents)| cents).sum();
+                if sum != total {
+                    return Err(bad_request(&format!(
+                        "Installments add up to {} but the plan amount is {}",
+                        amount::format_cents(sum),
+                        amount::format_cents(total)
+                    )));
+                }
+            }
+            listed
+        }
+        (None, Some(count), Some(first_due_date)) => {
+            let total = total.ok_or_else(|| bad_request("An amount is required to split into installments"))?;
+            if !(1..=60).contains(&count) {
+                return Err(bad_request("count must be between 1 and 60"));
+            }
+            let interval = body.interval_months.unwrap_or(1);
+            if !(1..=12).contains(&interval) {
+                return Err(bad_request("interval_months must be between 1 and 12"));
+            }
+            if total < i64::from(count) {
+                return Err(bad_request("Amount is too small to split into that many installments"));
+            }
+            let mut generated = Vec::with_capacity(count as usize);
+            for (index, cents) in split(total, count.into()).into_iter().enumerate() {
+                let due_date = add_months(first_due_date, index as u32 * interval as u32)
+                    .ok_or_else(|| bad_request("Due date is out of range"))?;
+                generated.push((due_date, cents));
+            }
+            generated
+        }
+        _ => return Err(bad_request("Give either installments, or count and first_due_date")),
+    };
+
+    if installments.is_empty() {
+        return Err(bad_request("A payment plan needs at least one installment"));
+    }
+    installments.sort_by_key(|(due_date, _)| *due_date);
+    Ok(installments)
+}
+
+async fn plan_response(
+    conn: &mut PgConnection,
+    plan: PaymentPlan,
+) -> Result<serde_json::Value, (StatusCode, Json<serde_json::Value>)> {
+    let plan = installments::with_installments(conn, vec![plan]).await?.pop();
+    Ok(json!({
+        "status": "success",
+        "data": json!({
+            "payment_plan": plan
+        })
+    }))
+}
+
+pub async fn create_payment_plan(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(student_id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<CreatePaymentPlanSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let student = sqlx::query_as!(
+        Student,
+        "SELECT * FROM \"students\" WHERE id = $1",
+        student_id
+    )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No student found with the provided ID"))?;
+    let payment_for = catalogs::canonical(&mut tx, "payment_for", &body.payment_for).await?;
+
+    let active = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM \"payment_plans\" WHERE student_id = $1 AND payment_for = $2 AND status = 'active')",
+        student.id,
+        payment_for
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .unwrap_or(false);
+    if active {
+        return Err(conflict(&format!("Student already has an active payment plan for {}", payment_for)));
+    }
+
+    let total = match (&body.amount, &body.installments) {
+        (Some(value), _) => Some(positive_cents("amount", value)?),
+        (None, Some(_)) => None,
+        (None, None) => {
+            let outstanding = outstanding_cents(&mut tx, student.id, &payment_for).await?;
+            if outstanding <= 0 {
+                return Err(bad_request(&format!("Nothing is outstanding for {}; give an amount", payment_for)));
+            }
+            Some(outstanding)
+        }
+    };
+    let schedule = schedule(&body, total)?;
+    let total: i64 = schedule.iter().map(|(_, cents)| cents).sum();
+
+    let plan = sqlx::query_as!(
+        PaymentPlan,
+        "INSERT INTO \"payment_plans\" (student_id,payment_for,total_amount,start_date,note,created_by) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+        student.id,
+        payment_for,
+        amount::format_cents(total),
+        body.start_date.unwrap_or_else(|| Utc::now().date_naive()),
+        body.note,
+        user.username
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    for (index, (due_date, cents)) in schedule.iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO \"plan_installments\" (plan_id,sequence,due_date,amount) VALUES ($1, $2, $3, $4)",
+            plan.id,
+            index as i32 + 1,
+            due_date,
+            amount::format_cents(*cents)
+        )
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+    }
+
+    installments::rebuild(&mut tx, student.id, &payment_for).await?;
+
+    let plan = lock_plan(&mut tx, plan.id).await?;
+    let plan_id = plan.id;
+    let response = plan_response(&mut tx, plan).await?;
+    log_event(&mut tx, &ctx, "create", "payment_plan", &plan_id.to_string(), None, Some(response["data"]["payment_plan"].clone()))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+async fn lock_plan(
+    conn: &mut PgConnection,
+    id: i32,
+) -> Result<PaymentPlan, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as!(
+        PaymentPlan,
+        "SELECT * FROM \"payment_plans\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No payment plan found with the provided ID"))
+}
+
+pub async fn get_student_payment_plans(
+    State(data): State<Arc<AppState>>,
+    Path(student_id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut conn = data.db.acquire().await.map_err(db_error)?;
+
+    let plans = sqlx::query_as!(
+        PaymentPlan,
+        "SELECT * FROM \"payment_plans\" WHERE student_id = $1 ORDER BY start_date DESC, id DESC",
+        student_id
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    let plans = installments::with_installments(&mut conn, plans).await?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "payment_plans": plans
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn get_payment_plan(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut conn = data.db.acquire().await.map_err(db_error)?;
+
+    let plan = sqlx::query_as!(
+        PaymentPlan,
+        "SELECT * FROM \"payment_plans\" WHERE id = $1",
+        id
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No payment plan found with the provided ID"))?;
+
+    Ok(Json(plan_response(&mut conn, plan).await?))
+}
+
+// Payments that were covering the cancelled plan flow on to the student's other plans
+// for the same category, if any.
+pub async fn cancel_payment_plan(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    body: Option<Json<CancelPaymentPlanSchema>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let reason = body.and_then(|Json(body)| body.reason);
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_plan(&mut tx, id).await?;
+    if before.status == "cancelled" {
+        return Err(conflict("Payment plan is already cancelled"));
+    }
+
+    sqlx::query!(
+        "DELETE FROM \"installment_allocations\" a USING \"plan_installments\" i WHERE a.installment_id = i.id AND i.plan_id = $1",
+        id
+    )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    let plan = sqlx::query_as!(
+        PaymentPlan,
+        "UPDATE \"payment_plans\" SET status = 'cancelled', note = COALESCE($1, note), updated_at = NOW() WHERE id = $2 RETURNING *",
+        reason,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    installments::rebuild(&mut tx, plan.student_id, &plan.payment_for).await?;
+
+    log_event(&mut tx, &ctx, "cancel", "payment_plan", &id.to_string(), Some(snapshot(&before)), Some(snapshot(&plan)))
+        .await
+        .map_err(db_error)?;
+
+    let response = plan_response(&mut tx, plan).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(Json(response))
+}
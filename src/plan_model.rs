@@ -0,0 +1,73 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct PaymentPlan {
+    pub id: i32,
+    pub student_id: i32,
+    pub payment_for: String,
+    pub total_amount: String,
+    pub start_date: NaiveDate,
+    pub status: String,
+    pub note: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    pub created_by: String,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct PlanInstallment {
+    pub id: i32,
+    pub plan_id: i32,
+    pub sequence: i32,
+    pub due_date: NaiveDate,
+    pub amount: String,
+}
+
+// An installment with what has been allocated to it so far.
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct InstallmentStatus {
+    pub id: i32,
+    pub plan_id: i32,
+    pub sequence: i32,
+    pub due_date: NaiveDate,
+    pub amount: String,
+    pub paid: String,
+    pub outstanding: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentPlanWithInstallments {
+    #[serde(flatten)]
+    pub plan: PaymentPlan,
+    pub installments: Vec<InstallmentStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InstallmentSchema {
+    pub due_date: NaiveDate,
+    pub amount: String,
+}
+
+// Either list the installments, or give `count` and `first_due_date` to split the
+// amount evenly at `interval_months` apart.
+#[derive(Debug, Deserialize)]
+pub struct CreatePaymentPlanSchema {
+    pub payment_for: String,
+    // Defaults to what the student still owes for `payment_for`.
+    pub amount: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    pub note: Option<String>,
+    pub installments: Option<Vec<InstallmentSchema>>,
+    pub count: Option<i32>,
+    pub first_due_date: Option<NaiveDate>,
+    pub interval_months: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelPaymentPlanSchema {
+    pub reason: Option<String>,
+}
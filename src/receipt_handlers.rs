@@ -0,0 +1,427 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::Json;
+use axum::response::IntoResponse;
+use serde_json::json;
+use sqlx::PgConnection;
+use crate::AppState;
+use crate::amount;
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::receipt_pdf::{self, ReceiptTemplate};
+use crate::receipt_model::{CreateReceiptSeriesSchema, DuplicateReceipt, ReceiptGap, ReceiptSeries, UpdateReceiptSeriesSchema};
+use crate::receipt_token;
+use crate::receipts::{validate_prefix, validate_scope};
+use crate::record_model::Record;
+use crate::response::{bad_request, conflict, db_error, not_found};
+
+fn validate_padding(padding: i32) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if (1..=12).contains(&padding) {
+        Ok(())
+    } else {
+        Err(bad_request("Padding must be between 1 and 12"))
+    }
+}
+
+async fn ensure_unique_name(
+    conn: &mut PgConnection,
+    name: &str,
+    except_id: Option<i32>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let exists = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM \"receipt_series\" WHERE name = $1 AND id IS DISTINCT FROM $2)",
+        name,
+        except_id
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .unwrap_or(false);
+
+    if exists {
+        return Err(conflict("Receipt series with that name already exists"));
+    }
+    Ok(())
+}
+
+// Legacy series print their prefix with no '-' added, so "OR-" or "OR-MAIN-" would
+// print numbers starting the same way as a new "OR" series.
+async fn ensure_unique_prefix(
+    conn: &mut PgConnection,
+    prefix: &str,
+    except_id: Option<i32>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let exists = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM \"receipt_series\" WHERE (LOWER(RTRIM(prefix, '-')) = LOWER($1) OR LOWER(prefix) LIKE LOWER($1) || '-%') AND id IS DISTINCT FROM $2)",
+        prefix,
+        except_id
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .unwrap_or(false);
+
+    if exists {
+        return Err(conflict("Receipt series with that prefix already exists"));
+    }
+    Ok(())
+}
+
+// Only one series can be the default; the partial unique index enforces it.
+async fn clear_default(conn: &mut PgConnection) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query!("UPDATE \"receipt_series\" SET is_default = FALSE, updated_at = NOW() WHERE is_default")
+        .execute(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    Ok(())
+}
+
+pub async fn get_receipt_series(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let series = sqlx::query_as!(
+        ReceiptSeries,
+        "SELECT * FROM \"receipt_series\" ORDER BY id"
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "series": series
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn create_receipt_series(
+    State(data): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Json(body): Json<CreateReceiptSeriesSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if body.name.trim().is_empty() {
+        return Err(bad_request("Name is required"));
+    }
+    validate_prefix(&body.prefix)?;
+    let scope = body.scope.unwrap_or_else(|| "global".to_string());
+    validate_scope(&scope)?;
+    let padding = body.padding.unwrap_or(6);
+    validate_padding(padding)?;
+    let is_default = body.is_default.unwrap_or(false);
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    ensure_unique_name(&mut tx, &body.name, None).await?;
+    ensure_unique_prefix(&mut tx, &body.prefix, None).await?;
+    if is_default {
+        clear_default(&mut tx).await?;
+    }
+
+    let series = sqlx::query_as!(
+        ReceiptSeries,
+        "INSERT INTO \"receipt_series\" (name,prefix,scope,yearly_reset,padding,is_default) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+        body.name,
+        body.prefix,
+        scope,
+        body.yearly_reset.unwrap_or(true),
+        padding,
+        is_default
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "create", "receipt_series", &series.id.to_string(), None, Some(snapshot(&series)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "series": series
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+// Numbers already handed out keep their original format. Padding can still change
+// afterwards, but prefix, scope and yearly reset decide which numbering stream a
+// receipt falls into, so they are fixed once the series has issued a number.
+pub async fn update_receipt_series(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<UpdateReceiptSeriesSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if let Some(scope) = &body.scope {
+        validate_scope(scope)?;
+    }
+    if let Some(padding) = body.padding {
+        validate_padding(padding)?;
+    }
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = sqlx::query_as!(
+        ReceiptSeries,
+        "SELECT * FROM \"receipt_series\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No receipt series found with the provided ID"))?;
+
+    if let Some(name) = &body.name {
+        ensure_unique_name(&mut tx, name, Some(id)).await?;
+    }
+    // Resending a legacy prefix as it is leaves the series in its old format.
+    let prefix = body.prefix.as_ref().filter(|prefix| **prefix != before.prefix);
+    if let Some(prefix) = prefix {
+        validate_prefix(prefix)?;
+        ensure_unique_prefix(&mut tx, prefix, Some(id)).await?;
+    }
+
+    let renumbers = prefix.is_some()
+        || body.scope.as_ref().is_some_and(|scope| *scope != before.scope)
+        || body.yearly_reset.is_some_and(|yearly_reset| yearly_reset != before.yearly_reset);
+    if renumbers {
+        let issued = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM \"receipt_counters\" WHERE series_id = $1)",
+            id
+        )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_error)?
+            .unwrap_or(false);
+
+        if issued {
+            return Err(conflict(&format!("Receipt series {} has already issued numbers; its prefix, scope and yearly reset can no longer change", before.name)));
+        }
+    }
+    if body.is_default == Some(true) && !before.is_default {
+        clear_default(&mut tx).await?;
+    }
+
+    let series = sqlx::query_as!(
+        ReceiptSeries,
+        "UPDATE \"receipt_series\" SET name = COALESCE($1, name), prefix = COALESCE($2, prefix), legacy_format = legacy_format AND $2::VARCHAR IS NULL, scope = COALESCE($3, scope), yearly_reset = COALESCE($4, yearly_reset), padding = COALESCE($5, padding), is_default = COALESCE($6, is_default), active = COALESCE($7, active), updated_at = NOW() WHERE id = $8 RETURNING *",
+        body.name,
+        prefix,
+        body.scope,
+        body.yearly_reset,
+        body.padding,
+        body.is_default,
+        body.active,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "update", "receipt_series", &id.to_string(), Some(snapshot(&before)), Some(snapshot(&series)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "series": series
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Every number between 1 and a counter's last value should belong to exactly one
+// record. Soft deleted records still hold their number, so a gap means a record was
+// purged or its receipt columns were changed outside the API.
+pub async fn get_receipt_report(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let gaps = sqlx::query_as::<_, ReceiptGap>(
+        "WITH missing AS (
+            SELECT c.series_id, c.scope_key, c.year, n.sequence
+            FROM \"receipt_counters\" c
+            CROSS JOIN LATERAL generate_series(1::BIGINT, c.last_number) AS n(sequence)
+            WHERE NOT EXISTS (
+                SELECT 1 FROM \"records\" r
+                WHERE r.or_series_id = c.series_id AND r.or_scope_key = c.scope_key
+                  AND r.or_year = c.year AND r.or_sequence = n.sequence
+            )
+        ), runs AS (
+            SELECT *, sequence - ROW_NUMBER() OVER (PARTITION BY series_id, scope_key, year ORDER BY sequence) AS run
+            FROM missing
+        )
+        SELECT series_id, scope_key, year, MIN(sequence) AS missing_from, MAX(sequence) AS missing_to
+        FROM runs
+        GROUP BY series_id, scope_key, year, run
+        ORDER BY series_id, scope_key, year, missing_from"
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let duplicates = sqlx::query_as::<_, DuplicateReceipt>(
+        "SELECT or_series_id AS series_id, or_scope_key AS scope_key, or_year AS year, or_sequence AS sequence, ARRAY_AGG(id ORDER BY id) AS record_ids
+        FROM \"records\"
+        WHERE or_sequence IS NOT NULL
+        GROUP BY or_series_id, or_scope_key, or_year, or_sequence
+        HAVING COUNT(*) > 1
+        ORDER BY or_series_id, or_scope_key, or_year, or_sequence"
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "clean": gaps.is_empty() && duplicates.is_empty(),
+            "gaps": gaps,
+            "duplicates": duplicates
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Read on every request so a changed template takes effect without a restart.
+async fn load_template(path: Option<&str>) -> Result<ReceiptTemplate, (StatusCode, Json<serde_json::Value>)> {
+    let source = match path {
+        Some(path) => tokio::fs::read_to_string(path).await.map_err(|e| {
+            let error_response = json!({
+                "status": "fail",
+                "message": format!("Receipt template error: {}", e),
+            });
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+        })?,
+        None => receipt_pdf::DEFAULT_TEMPLATE.to_string(),
+    };
+
+    serde_json::from_str(&source).map_err(|e| {
+        let error_response = json!({
+            "status": "fail",
+            "message": format!("Receipt template error: {}", e),
+        });
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+    })
+}
+
+pub async fn get_record_receipt_pdf(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let record = sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE id = $1 AND deleted_at IS NULL",
+        id
+    )
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No record found with the provided ID"))?;
+
+    let or_number = match &record.or_number {
+        Some(or_number) => or_number.to_owned(),
+        None => {
+            return Err(conflict("Record has no OR number; it was created before receipt numbering was configured"));
+        }
+    };
+
+    let cents = amount::parse_cents(&record.amount).ok_or_else(|| {
+        let error_response = json!({
+            "status": "fail",
+            "message": format!("Amount {} is not a valid number", record.amount),
+        });
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(error_response))
+    })?;
+
+    let template = load_template(data.env.receipt_template_path.as_deref()).await?;
+
+    let token = receipt_token::sign(&record, &data.env.jwt_secret).map_err(|e| {
+        let error_response = json!({
+            "status": "fail",
+            "message": format!("Token error: {}", e),
+        });
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+    })?;
+    let verify_url = format!("{}/api/verify/{}", data.env.public_base_url, token);
+
+    let pdf = receipt_pdf::render(&record, &template, &verify_url, cents).map_err(|e| {
+        let error_response = json!({
+            "status": "fail",
+            "message": format!("PDF error: {}", e),
+        });
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, format!("inline; filename=\"{}.pdf\"", or_number)),
+        ],
+        pdf,
+    ))
+}
+
+// Public: anyone holding a receipt can check it, so only details already printed on
+// the receipt are returned and never the student's name.
+pub async fn verify_receipt_handler(
+    State(data): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let claims = receipt_token::verify(&token, &data.env.jwt_secret).map_err(|_| {
+        bad_request("Invalid receipt token; this receipt was not issued by us or has been altered")
+    })?;
+
+    let record = sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE id = $1",
+        claims.rid
+    )
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let voided = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM \"records\" WHERE reverses_record_id = $1 AND entry_type = 'void' AND deleted_at IS NULL)",
+        claims.rid
+    )
+        .fetch_one(&data.db)
+        .await
+        .map_err(db_error)?
+        .unwrap_or(false);
+
+    let reason = match &record {
+        None => Some("No matching payment record exists".to_string()),
+        Some(record) if record.deleted_at.is_some() => Some("This payment has been cancelled".to_string()),
+        Some(_) if voided => Some("This payment has been voided".to_string()),
+        Some(record) => receipt_token::mismatch(&claims, record)
+            .map(|field| format!("The {} on this receipt does not match our records", field)),
+    };
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "authentic": reason.is_none(),
+            "reason": reason,
+            "receipt": json!({
+                "or_number": claims.or,
+                "amount": claims.amt,
+                "payment_for": record.as_ref().map(|record| record.payment_for.to_owned()),
+                "paidAt": record.as_ref().and_then(|record| record.created_at),
+            })
+        })
+    });
+
+    Ok(Json(json_response))
+}
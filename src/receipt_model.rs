@@ -0,0 +1,60 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct ReceiptSeries {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scope: String,
+    pub yearly_reset: bool,
+    pub padding: i32,
+    pub is_default: bool,
+    pub active: bool,
+    // Series created before prefixes had to be unique print their prefix with no '-'.
+    pub legacy_format: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateReceiptSeriesSchema {
+    pub name: String,
+    pub prefix: String,
+    pub scope: Option<String>,
+    pub yearly_reset: Option<bool>,
+    pub padding: Option<i32>,
+    pub is_default: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateReceiptSeriesSchema {
+    pub name: Option<String>,
+    pub prefix: Option<String>,
+    pub scope: Option<String>,
+    pub yearly_reset: Option<bool>,
+    pub padding: Option<i32>,
+    pub is_default: Option<bool>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ReceiptGap {
+    pub series_id: i32,
+    pub scope_key: String,
+    pub year: i32,
+    pub missing_from: i64,
+    pub missing_to: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DuplicateReceipt {
+    pub series_id: Option<i32>,
+    pub scope_key: Option<String>,
+    pub year: Option<i32>,
+    pub sequence: Option<i64>,
+    pub record_ids: Vec<i32>,
+}
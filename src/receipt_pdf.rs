@@ -0,0 +1,195 @@
+use printpdf::{BuiltinFont, Line, Mm, PdfDocument, Point, Rect};
+use qrcode::{Color, QrCode};
+use serde::Deserialize;
+use crate::amount;
+use crate::record_model::Record;
+
+// Used when `RECEIPT_TEMPLATE_PATH` is not set.
+pub const DEFAULT_TEMPLATE: &str = include_str!("../templates/receipt.json");
+
+// Positions are in millimetres from the top left corner of the page. Text may contain
+// `{placeholder}`s, see `placeholders` for the available names.
+#[derive(Debug, Deserialize)]
+pub struct ReceiptTemplate {
+    pub title: String,
+    pub page_width_mm: f32,
+    pub page_height_mm: f32,
+    pub currency: String,
+    pub elements: Vec<TemplateElement>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TemplateElement {
+    Text {
+        text: String,
+        x_mm: f32,
+        y_mm: f32,
+        size: f32,
+        #[serde(default)]
+        bold: bool,
+    },
+    Line {
+        from_mm: [f32; 2],
+        to_mm: [f32; 2],
+        #[serde(default = "default_thickness")]
+        thickness: f32,
+    },
+    // QR code pointing at the public verification URL of this receipt.
+    Qr {
+        x_mm: f32,
+        y_mm: f32,
+        size_mm: f32,
+    },
+}
+
+fn default_thickness() -> f32 {
+    1.0
+}
+
+const ONES: [&str; 20] = [
+    "Zero", "One", "Two", "Three", "Four", "Five", "Six", "Seven", "Eight", "Nine", "Ten",
+    "Eleven", "Twelve", "Thirteen", "Fourteen", "Fifteen", "Sixteen", "Seventeen", "Eighteen", "Nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "Twenty", "Thirty", "Forty", "Fifty", "Sixty", "Seventy", "Eighty", "Ninety",
+];
+const SCALES: [&str; 5] = ["", "Thousand", "Million", "Billion", "Trillion"];
+
+fn below_thousand(n: u64) -> String {
+    let mut words = Vec::new();
+    if n >= 100 {
+        words.push(format!("{} Hundred", ONES[(n / 100) as usize]));
+    }
+    let rest = n % 100;
+    if rest >= 20 {
+        words.push(match rest % 10 {
+            0 => TENS[(rest / 10) as usize].to_string(),
+            ones => format!("{}-{}", TENS[(rest / 10) as usize], ONES[ones as usize]),
+        });
+    } else if rest > 0 {
+        words.push(ONES[rest as usize].to_string());
+    }
+    words.join(" ")
+}
+
+fn number_to_words(mut n: u64) -> String {
+    if n == 0 {
+        return ONES[0].to_string();
+    }
+
+    let mut groups = Vec::new();
+    let mut scale = 0;
+    while n > 0 {
+        let group = n % 1000;
+        if group > 0 {
+            let words = below_thousand(group);
+            groups.push(match SCALES.get(scale) {
+                Some(name) if !name.is_empty() => format!("{} {}", words, name),
+                _ => words,
+            });
+        }
+        n /= 1000;
+        scale += 1;
+    }
+    groups.reverse();
+    groups.join(" ")
+}
+
+// "One Thousand Five Hundred Pesos and 50/100 Only"
+pub fn amount_in_words(cents: i64, currency: &str) -> String {
+    let (whole, cents) = (cents.unsigned_abs() / 100, cents.unsigned_abs() % 100);
+    if cents == 0 {
+        format!("{} {} Only", number_to_words(whole), currency)
+    } else {
+        format!("{} {} and {:02}/100 Only", number_to_words(whole), currency, cents)
+    }
+}
+
+fn placeholders(record: &Record, template: &ReceiptTemplate, verify_url: &str, cents: i64) -> Vec<(&'static str, String)> {
+    let student_name = if record.mi.trim().is_empty() {
+        format!("{} {}", record.first_name, record.last_name)
+    } else {
+        format!("{} {}. {}", record.first_name, record.mi.trim_end_matches('.'), record.last_name)
+    };
+
+    vec![
+        ("{or_number}", record.or_number.clone().unwrap_or_default()),
+        ("{record_id}", record.id.to_string()),
+        ("{date}", record.created_at.map(|date| date.format("%B %d, %Y").to_string()).unwrap_or_default()),
+        ("{student_name}", student_name),
+        ("{first_name}", record.first_name.to_owned()),
+        ("{last_name}", record.last_name.to_owned()),
+        ("{mi}", record.mi.to_owned()),
+        ("{course}", record.course.to_owned()),
+        ("{year_level}", record.year_level.to_owned()),
+        ("{payment_for}", record.payment_for.to_owned()),
+        ("{amount}", amount::format_grouped(cents)),
+        ("{amount_in_words}", amount_in_words(cents, &template.currency)),
+        ("{received_by}", record.received_by.to_owned()),
+        ("{verify_url}", verify_url.to_string()),
+    ]
+}
+
+fn fill(text: &str, values: &[(&'static str, String)]) -> String {
+    values
+        .iter()
+        .fold(text.to_string(), |text, (key, value)| text.replace(key, value))
+}
+
+// Renders the receipt with the built-in PDF fonts, so nothing has to be embedded or
+// fetched. The caller has already checked that the amount parses.
+pub fn render(
+    record: &Record,
+    template: &ReceiptTemplate,
+    verify_url: &str,
+    cents: i64,
+) -> Result<Vec<u8>, String> {
+    let values = placeholders(record, template, verify_url, cents);
+    let code = QrCode::new(verify_url.as_bytes()).map_err(|e| e.to_string())?;
+    let height = template.page_height_mm;
+
+    let (doc, page, layer) = PdfDocument::new(
+        fill(&template.title, &values),
+        Mm(template.page_width_mm),
+        Mm(height),
+        "Receipt",
+    );
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+    let layer = doc.get_page(page).get_layer(layer);
+
+    for element in &template.elements {
+        match element {
+            TemplateElement::Text { text, x_mm, y_mm, size, bold: is_bold } => {
+                let font = if *is_bold { &bold } else { &regular };
+                layer.use_text(fill(text, &values), *size, Mm(*x_mm), Mm(height - y_mm), font);
+            }
+            TemplateElement::Line { from_mm, to_mm, thickness } => {
+                layer.set_outline_thickness(*thickness);
+                layer.add_line(Line {
+                    points: vec![
+                        (Point::new(Mm(from_mm[0]), Mm(height - from_mm[1])), false),
+                        (Point::new(Mm(to_mm[0]), Mm(height - to_mm[1])), false),
+                    ],
+                    is_closed: false,
+                });
+            }
+            TemplateElement::Qr { x_mm, y_mm, size_mm } => {
+                let width = code.width();
+                let module = size_mm / width as f32;
+                // One filled square per dark module, row by row from the top.
+                for (i, color) in code.to_colors().into_iter().enumerate() {
+                    if color != Color::Dark {
+                        continue;
+                    }
+                    let left = x_mm + (i % width) as f32 * module;
+                    let top = height - y_mm - (i / width) as f32 * module;
+                    layer.add_rect(Rect::new(Mm(left), Mm(top - module), Mm(left + module), Mm(top)));
+                }
+            }
+        }
+    }
+
+    doc.save_to_bytes().map_err(|e| e.to_string())
+}
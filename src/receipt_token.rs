@@ -0,0 +1,55 @@
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use crate::record_model::Record;
+
+const AUDIENCE: &str = "receipt-verification";
+
+// Signed with the same secret as login tokens. The audience keeps the two apart, and
+// the missing `sub` means a receipt token is never accepted by the auth middleware.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReceiptClaims {
+    pub aud: String,
+    pub rid: i32,
+    pub or: Option<String>,
+    pub amt: String,
+    // When the payment was recorded, as a unix timestamp.
+    pub ts: i64,
+    pub iat: usize,
+}
+
+pub fn sign(record: &Record, secret: &str) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = ReceiptClaims {
+        aud: AUDIENCE.to_string(),
+        rid: record.id,
+        or: record.or_number.clone(),
+        amt: record.amount.to_owned(),
+        ts: record.created_at.map(|date| date.timestamp()).unwrap_or_default(),
+        iat: chrono::Utc::now().timestamp() as usize,
+    };
+
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_ref()))
+}
+
+// Receipts stay verifiable for as long as the record exists, so there is no expiry.
+pub fn verify(token: &str, secret: &str) -> Result<ReceiptClaims, jsonwebtoken::errors::Error> {
+    let mut validation = Validation::default();
+    validation.validate_exp = false;
+    validation.required_spec_claims.clear();
+    validation.set_audience(&[AUDIENCE]);
+
+    decode::<ReceiptClaims>(token, &DecodingKey::from_secret(secret.as_ref()), &validation)
+        .map(|data| data.claims)
+}
+
+// Which of the signed details no longer match the record, if any.
+pub fn mismatch(claims: &ReceiptClaims, record: &Record) -> Option<&'static str> {
+    if claims.amt != record.amount {
+        Some("amount")
+    } else if claims.or != record.or_number {
+        Some("OR number")
+    } else if Some(claims.ts) != record.created_at.map(|date| date.timestamp()) {
+        Some("payment date")
+    } else {
+        None
+    }
+}
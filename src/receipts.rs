@@ -0,0 +1,236 @@
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::prelude::*;
+use sqlx::PgConnection;
+use crate::model::User;
+use crate::receipt_model::ReceiptSeries;
+use crate::response::{bad_request, conflict, db_error};
+
+pub const SCOPES: [&str; 3] = ["global", "cashier", "office"];
+
+#[derive(Debug)]
+pub struct AllocatedReceipt {
+    pub number: String,
+    pub series_id: i32,
+    pub scope_key: String,
+    pub year: i32,
+    pub sequence: i64,
+}
+
+pub fn validate_prefix(prefix: &str) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Ok(())
+    } else {
+        Err(bad_request("Prefix must be made of letters and digits only"))
+    }
+}
+
+pub fn validate_scope(scope: &str) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if SCOPES.contains(&scope) {
+        Ok(())
+    } else {
+        Err(bad_request(&format!("Invalid scope: {}. Expected one of: {}", scope, SCOPES.join(", "))))
+    }
+}
+
+// The office or cashier a scoped series numbers by, trimmed and uppercased once so
+// "Main" and "MAIN" share one counter instead of printing the same numbers twice.
+fn scope_key(series: &ReceiptSeries, user: &User) -> Result<String, (StatusCode, Json<serde_json::Value>)> {
+    let key = match series.scope.as_str() {
+        "cashier" => user.username.trim().to_uppercase(),
+        "office" => user.office.as_deref().unwrap_or_default().trim().to_uppercase(),
+        _ => return Ok(String::new()),
+    };
+
+    if key.is_empty() {
+        return Err(bad_request(&format!(
+            "Receipt series {} is numbered per office but user {} has no office",
+            series.name, user.username
+        )));
+    }
+    if key.contains('-') {
+        return Err(bad_request(&format!(
+            "Receipt series {} is numbered per {} but {:?} contains '-', which separates the parts of an OR number",
+            series.name, series.scope, key
+        )));
+    }
+    Ok(key)
+}
+
+// e.g. "OR-MAIN-2026-000042": prefix, then the office or cashier for scoped series,
+// then the year for series that reset, then the zero padded sequence. Prefixes are
+// unique and neither they nor scope keys contain '-', so numbers of different
+// streams never collide. Legacy series print their prefix as it is, e.g. "OR2026-000042".
+fn format_number(series: &ReceiptSeries, scope_key: &str, year: i32, sequence: i64) -> String {
+    let mut number = series.prefix.to_owned();
+    if !series.legacy_format {
+        number.push('-');
+    }
+    if !scope_key.is_empty() {
+        number.push_str(scope_key);
+        number.push('-');
+    }
+    if series.yearly_reset {
+        number.push_str(&format!("{}-", year));
+    }
+    number.push_str(&format!("{:0width$}", sequence, width = series.padding as usize));
+    number
+}
+
+// Hands out the next number of the requested series, or of the default series when
+// none is given. Returns `None` when no series applies so records can still be created
+// before any series is configured.
+//
+// The counter row stays locked until the caller's transaction ends, so concurrent
+// cashiers on the same stream queue up behind it and a rolled back insert gives its
+// number back instead of leaving a gap.
+pub async fn allocate(
+    conn: &mut PgConnection,
+    user: &User,
+    series_id: Option<i32>,
+) -> Result<Option<AllocatedReceipt>, (StatusCode, Json<serde_json::Value>)> {
+    let series = match series_id {
+        Some(series_id) => {
+            let series = sqlx::query_as!(
+                ReceiptSeries,
+                "SELECT * FROM \"receipt_series\" WHERE id = $1",
+                series_id
+            )
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(db_error)?
+                .ok_or_else(|| bad_request(&format!("Receipt series {} does not exist", series_id)))?;
+
+            if !series.active {
+                return Err(bad_request(&format!("Receipt series {} is not active", series.name)));
+            }
+            series
+        }
+        None => {
+            let series = sqlx::query_as!(
+                ReceiptSeries,
+                "SELECT * FROM \"receipt_series\" WHERE is_default AND active"
+            )
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(db_error)?;
+
+            match series {
+                Some(series) => series,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    let scope_key = scope_key(&series, user)?;
+    // The local calendar year (the server's TZ), so receipts issued on New Year's
+    // Eve are not numbered into the year that has only started in UTC.
+    let year = if series.yearly_reset { Local::now().year() } else { 0 };
+
+    let sequence = sqlx::query_scalar!(
+        "INSERT INTO \"receipt_counters\" (series_id,scope_key,year,last_number) VALUES ($1, $2, $3, 1) ON CONFLICT (series_id, scope_key, year) DO UPDATE SET last_number = \"receipt_counters\".last_number + 1 RETURNING last_number",
+        series.id,
+        scope_key,
+        year
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    Ok(Some(AllocatedReceipt {
+        number: format_number(&series, &scope_key, year, sequence),
+        series_id: series.id,
+        scope_key,
+        year,
+        sequence,
+    }))
+}
+
+// Maps a failed record insert. Allocation should never hand out a number twice, but if
+// a series was misconfigured outside the API the cashier gets a conflict they can act
+// on rather than a generic database error.
+pub fn insert_error(receipt: Option<&AllocatedReceipt>, e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    match &e {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => conflict(&match receipt {
+            Some(receipt) => format!("Receipt number {} is already in use; check the receipt series configuration", receipt.number),
+            None => format!("Record conflicts with an existing one: {}", db_err.message()),
+        }),
+        _ => db_error(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(scope: &str, yearly_reset: bool) -> ReceiptSeries {
+        ReceiptSeries {
+            id: 1,
+            name: "Main".to_string(),
+            prefix: "OR".to_string(),
+            scope: scope.to_string(),
+            yearly_reset,
+            padding: 6,
+            is_default: true,
+            active: true,
+            legacy_format: false,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn user(username: &str, office: Option<&str>) -> User {
+        User {
+            id: uuid::Uuid::nil(),
+            name: username.to_string(),
+            email: format!("{}@example.com", username),
+            username: username.to_string(),
+            password: String::new(),
+            role: "user".to_string(),
+            photo: None,
+            created_at: None,
+            updated_at: None,
+            office: office.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn formats_each_part_of_the_number() {
+        assert_eq!(format_number(&series("global", true), "", 2026, 42), "OR-2026-000042");
+        assert_eq!(format_number(&series("global", false), "", 0, 42), "OR-000042");
+        assert_eq!(format_number(&series("office", true), "MAIN", 2026, 42), "OR-MAIN-2026-000042");
+        assert_eq!(format_number(&series("cashier", false), "ANA", 0, 1234567), "OR-ANA-1234567");
+    }
+
+    #[test]
+    fn legacy_series_keep_printing_their_prefix_as_it_is() {
+        let mut legacy = series("global", true);
+        legacy.legacy_format = true;
+        assert_eq!(format_number(&legacy, "", 2026, 42), "OR2026-000042");
+
+        legacy.prefix = "OR/".to_string();
+        assert_eq!(format_number(&legacy, "", 2026, 42), "OR/2026-000042");
+    }
+
+    #[test]
+    fn scope_keys_differing_by_case_or_spacing_are_one_stream() {
+        let office = series("office", true);
+        assert_eq!(scope_key(&office, &user("ana", Some(" Main "))).unwrap(), "MAIN");
+        assert_eq!(scope_key(&office, &user("ben", Some("MAIN"))).unwrap(), "MAIN");
+
+        let cashier = series("cashier", true);
+        assert_eq!(scope_key(&cashier, &user("Ana", None)).unwrap(), "ANA");
+        assert_eq!(scope_key(&cashier, &user("ana", None)).unwrap(), "ANA");
+
+        assert_eq!(scope_key(&series("global", true), &user("ana", Some("Main"))).unwrap(), "");
+    }
+
+    #[test]
+    fn rejects_missing_offices_and_keys_containing_the_separator() {
+        let office = series("office", true);
+        assert!(scope_key(&office, &user("ana", None)).is_err());
+        assert!(scope_key(&office, &user("ana", Some("  "))).is_err());
+        assert!(scope_key(&office, &user("ana", Some("North-Wing"))).is_err());
+        assert!(scope_key(&series("cashier", true), &user("ana-m", None)).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use axum::http::StatusCode;
+use axum::Json;
+use serde_json::{json, Value};
+use crate::record_model::Record;
+use crate::response::bad_request;
+use crate::xlsx;
+
+// Columns an export can pick with `columns=`, by key, with their spreadsheet header.
+// Without a selection every column is exported in this order.
+pub const RECORD_FIELDS: [(&str, xlsx::Column); 18] = [
+    ("id", ("Id", 8.0)),
+    ("or_number", ("OR Number", 18.0)),
+    ("created_at", ("Created At", 17.0)),
+    ("updated_at", ("Updated At", 17.0)),
+    ("last_updated_by", ("Last Updated By", 16.0)),
+    ("student_id", ("Student Id", 10.0)),
+    ("first_name", ("First Name", 18.0)),
+    ("last_name", ("Last Name", 18.0)),
+    ("mi", ("MI", 5.0)),
+    ("course", ("Course", 10.0)),
+    ("year_level", ("Year Level", 10.0)),
+    ("academic_term_id", ("Academic Term Id", 10.0)),
+    ("payment_for", ("Payment For", 18.0)),
+    ("entry_type", ("Entry Type", 10.0)),
+    ("reverses_record_id", ("Reverses Record Id", 10.0)),
+    ("amount", ("Amount", 14.0)),
+    ("received_by", ("Received By", 16.0)),
+    ("finalized_at", ("Finalized At", 17.0)),
+];
+
+// Parses a comma-separated list of column keys into field keys, in the order given.
+pub fn select(columns: Option<&str>) -> Result<Vec<&'static str>, (StatusCode, Json<Value>)> {
+    let Some(columns) = columns.filter(|columns| !columns.trim().is_empty()) else {
+        return Ok(RECORD_FIELDS.iter().map(|(key, _)| *key).collect());
+    };
+
+    let mut selected: Vec<&'static str> = Vec::new();
+    for name in columns.split(',').map(|name| name.trim().to_lowercase()) {
+        let Some((key, _)) = RECORD_FIELDS.iter().find(|(key, _)| *key == name) else {
+            let keys: Vec<&str> = RECORD_FIELDS.iter().map(|(key, _)| *key).collect();
+            return Err(bad_request(&format!("Unknown column: {}. Expected any of: {}", name, keys.join(", "))));
+        };
+        if selected.contains(key) {
+            return Err(bad_request(&format!("Column listed twice: {}", key)));
+        }
+        selected.push(key);
+    }
+    Ok(selected)
+}
+
+// Keys come from `select`, so the fallback is never used.
+pub fn header(key: &str) -> xlsx::Column {
+    RECORD_FIELDS
+        .iter()
+        .find(|(field, _)| *field == key)
+        .map(|(_, column)| *column)
+        .unwrap_or(("", 12.0))
+}
+
+// Ids of other rows a record points at, which can be missing.
+pub fn link(record: &Record, key: &str) -> Option<i32> {
+    match key {
+        "student_id" => record.student_id,
+        "academic_term_id" => record.academic_term_id,
+        "reverses_record_id" => record.reverses_record_id,
+        _ => None,
+    }
+}
+
+// Timestamps of a record by column key.
+pub fn timestamp(record: &Record, key: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    match key {
+        "created_at" => record.created_at,
+        "updated_at" => record.updated_at,
+        "finalized_at" => record.finalized_at,
+        _ => None,
+    }
+}
+
+// The value as plain text, for CSV. Timestamps are RFC 3339 in UTC, amounts are
+// written as they were entered and missing values are empty.
+pub fn text(record: &Record, key: &str) -> String {
+    match key {
+        "id" => record.id.to_string(),
+        "or_number" => record.or_number.clone().unwrap_or_default(),
+        "created_at" | "updated_at" | "finalized_at" => {
+            timestamp(record, key).map(|value| value.to_rfc3339()).unwrap_or_default()
+        }
+        "student_id" | "academic_term_id" | "reverses_record_id" => {
+            link(record, key).map(|id| id.to_string()).unwrap_or_default()
+        }
+        "entry_type" => record.entry_type.to_owned(),
+        "last_updated_by" => record.last_updated_by.to_owned(),
+        "first_name" => record.first_name.to_owned(),
+        "last_name" => record.last_name.to_owned(),
+        "mi" => record.mi.to_owned(),
+        "course" => record.course.to_owned(),
+        "year_level" => record.year_level.to_owned(),
+        "payment_for" => record.payment_for.to_owned(),
+        "amount" => record.amount.to_owned(),
+        "received_by" => record.received_by.to_owned(),
+        _ => String::new(),
+    }
+}
+
+// The value as JSON, for NDJSON: ids stay numbers and missing values are null.
+pub fn value(record: &Record, key: &str) -> Value {
+    match key {
+        "id" => json!(record.id),
+        "or_number" => json!(record.or_number),
+        "created_at" | "updated_at" | "finalized_at" => json!(timestamp(record, key)),
+        "student_id" | "academic_term_id" | "reverses_record_id" => json!(link(record, key)),
+        _ => Value::String(text(record, key)),
+    }
+}
+
+
+// `delimiter=` takes a single character, or "tab". Quotes and line breaks are reserved
+// by RFC 4180.
+pub fn delimiter(value: Option<&str>) -> Result<char, (StatusCode, Json<Value>)> {
+    let value = match value {
+        None | Some("") => return Ok(','),
+        Some("tab") | Some("\\t") => return Ok('\t'),
+        Some(value) => value,
+    };
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !matches!(c, '"' | '\r' | '\n') => Ok(c),
+        _ => Err(bad_request("delimiter must be a single character other than a quote or line break, or \"tab\"")),
+    }
+}
+
+// One CSV line, CRLF-terminated. Fields holding the delimiter, a quote or a line break
+// are quoted, with quotes doubled.
+pub fn csv_line<'a>(values: impl Iterator<Item = &'a str>, delimiter: char) -> String {
+    let mut line = String::new();
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            line.push(delimiter);
+        }
+        if value.contains([delimiter, '"', '\r', '\n']) {
+            line.push('"');
+            line.push_str(&value.replace('"', "\"\""));
+            line.push('"');
+        } else {
+            line.push_str(value);
+        }
+    }
+    line.push_str("\r\n");
+    line
+}
+
+// The header row of column keys, after a UTF-8 byte order mark when `bom` is set so
+// Excel does not read the file as ANSI.
+pub fn csv_header(fields: &[&str], delimiter: char, bom: bool) -> String {
+    let mut header = if bom { "\u{feff}".to_string() } else { String::new() };
+    header.push_str(&csv_line(fields.iter().copied(), delimiter));
+    header
+}
+
+pub fn csv_record(record: &Record, fields: &[&str], delimiter: char) -> String {
+    let values: Vec<String> = fields.iter().map(|key| text(record, key)).collect();
+    csv_line(values.iter().map(String::as_str), delimiter)
+}
+
+// Written by hand rather than through a serde_json map, which would sort the keys
+// instead of keeping the selected column order.
+pub fn ndjson_record(record: &Record, fields: &[&str]) -> String {
+    let members: Vec<String> = fields
+        .iter()
+        .map(|key| format!("{}:{}", Value::from(*key), value(record, key)))
+        .collect();
+    format!("{{{}}}\n", members.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(values: &[&str], delimiter: char) -> String {
+        csv_line(values.iter().copied(), delimiter)
+    }
+
+    #[test]
+    fn plain_fields_are_not_quoted() {
+        assert_eq!(line(&["1", "Juan", "1500.00"], ','), "1,Juan,1500.00\r\n");
+        assert_eq!(line(&["", "x", ""], ','), ",x,\r\n");
+    }
+
+    #[test]
+    fn fields_with_reserved_characters_are_quoted() {
+        assert_eq!(line(&["Cruz, Juan", "BSIT"], ','), "\"Cruz, Juan\",BSIT\r\n");
+        assert_eq!(line(&["say \"hi\""], ','), "\"say \"\"hi\"\"\"\r\n");
+        assert_eq!(line(&["two\nlines", "cr\rhere"], ','), "\"two\nlines\",\"cr\rhere\"\r\n");
+    }
+
+    #[test]
+    fn quoting_follows_the_delimiter() {
+        assert_eq!(line(&["Cruz, Juan", "a;b"], ';'), "Cruz, Juan;\"a;b\"\r\n");
+        assert_eq!(line(&["Cruz, Juan", "tab\there"], '\t'), "Cruz, Juan\t\"tab\there\"\r\n");
+    }
+
+    #[test]
+    fn delimiter_defaults_to_comma_and_accepts_tab() {
+        assert_eq!(delimiter(None).unwrap(), ',');
+        assert_eq!(delimiter(Some("")).unwrap(), ',');
+        assert_eq!(delimiter(Some("tab")).unwrap(), '\t');
+        assert_eq!(delimiter(Some("\\t")).unwrap(), '\t');
+        assert_eq!(delimiter(Some(";")).unwrap(), ';');
+        assert_eq!(delimiter(Some("|")).unwrap(), '|');
+    }
+
+    #[test]
+    fn delimiter_rejects_quotes_line_breaks_and_longer_values() {
+        for value in ["\"", "\n", "\r", ";;", "comma"] {
+            assert_eq!(delimiter(Some(value)).unwrap_err().0, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[test]
+    fn header_starts_with_a_bom_only_when_asked() {
+        assert_eq!(csv_header(&["id", "amount"], ',', false), "id,amount\r\n");
+        let header = csv_header(&["id", "amount"], '\t', true);
+        assert!(header.as_bytes().starts_with(&[0xEF, 0xBB, 0xBF]));
+        assert_eq!(header.trim_start_matches('\u{feff}'), "id\tamount\r\n");
+    }
+
+    #[test]
+    fn every_field_can_be_selected() {
+        let keys: Vec<&str> = RECORD_FIELDS.iter().map(|(key, _)| *key).collect();
+        assert_eq!(select(None).unwrap(), keys);
+        assert_eq!(select(Some(&keys.join(","))).unwrap(), keys);
+        assert_eq!(select(Some(" OR_NUMBER, entry_type ")).unwrap(), vec!["or_number", "entry_type"]);
+        assert!(select(Some("id,id")).is_err());
+        assert!(select(Some("password")).is_err());
+    }
+}
@@ -1,22 +1,62 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use axum::body::Body;
-use axum::extract::State;
-use axum::http::{StatusCode, Response};
-use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode, Response};
+use axum::{Extension, Json};
 use axum::response::{IntoResponse};
+use chrono::{Duration, Utc};
+use futures_util::StreamExt;
 use serde_json::json;
 use tokio_util::codec::{BytesCodec, FramedRead};
 use tokio::fs::File;
 use crate::AppState;
-use crate::record_model::{CreateRecordSchema, DeleteRecordSchema, Record, UpdateRecordSchema};
+use crate::amount;
+use crate::approvals::{self, Outcome, ProposedChange};
+use crate::catalogs;
+use crate::duplicates::{self, Candidate, DuplicateCheck};
+use crate::etag::{etag, if_match};
+use crate::idempotency;
+use crate::export_model::ExportJob;
+use crate::exports;
+use crate::installments;
+use crate::ledger;
+use crate::receipts;
+use crate::record_columns;
+use crate::students;
+use crate::xlsx;
+use crate::terms;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::model::User;
+use crate::record_model::{CreateRecordSchema, DeleteRecordQuery, DeleteRecordSchema, LegacyUpdateRecordSchema, PatchRecordSchema, Record, RecordFilter, CsvExportQuery, DuplicateQuery, ExportColumnsQuery, SummaryExportQuery, UpdateRecordSchema};
+use crate::response::{bad_request, conflict, db_error, forbidden, not_found};
+
+// Payments cannot be dated in the future; a little slack covers clock skew.
+pub fn check_payment_date(body: &CreateRecordSchema) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match body.created_at {
+        Some(created_at) if created_at > Utc::now() + Duration::minutes(5) => {
+            Err(bad_request("created_at cannot be in the future"))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Inserts a payment and takes the next OR number of its series for `user`. Shared by
+// `create_record_handler` and by approved change requests.
+pub async fn apply_create(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    user: &User,
+    body: &CreateRecordSchema,
+) -> Result<Record, (StatusCode, Json<serde_json::Value>)> {
+    let receipt = receipts::allocate(&mut *conn, user, body.receipt_series_id).await?;
+    let student_id = students::resolve(&mut *conn, body.student_id, &body.first_name, &body.last_name, &body.mi).await?;
+    let academic_term_id = terms::resolve(&mut *conn, body.academic_term_id, body.created_at).await?;
 
-pub async fn create_record_handler(
-    State(data): State<Arc<AppState>>,
-    Json(body): Json<CreateRecordSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     let record = sqlx::query_as!(
         Record,
-        "INSERT INTO \"records\" (last_updated_by,first_name,last_name,mi,course,year_level,payment_for,amount,received_by) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING *",
+        "INSERT INTO \"records\" (last_updated_by,first_name,last_name,mi,course,year_level,payment_for,amount,received_by,or_number,or_series_id,or_scope_key,or_year,or_sequence,created_at,student_id,academic_term_id,import_batch_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, COALESCE($15, NOW()), $16, $17, $18) RETURNING *",
         body.last_updated_by.to_string(),
         body.first_name.to_string(),
         body.last_name.to_string(),
@@ -25,32 +65,117 @@ pub async fn create_record_handler(
         body.year_level.to_string(),
         body.payment_for.to_string(),
         body.amount,
-        body.received_by.to_string()
+        body.received_by.to_string(),
+        receipt.as_ref().map(|receipt| receipt.number.to_owned()),
+        receipt.as_ref().map(|receipt| receipt.series_id),
+        receipt.as_ref().map(|receipt| receipt.scope_key.to_owned()),
+        receipt.as_ref().map(|receipt| receipt.year),
+        receipt.as_ref().map(|receipt| receipt.sequence),
+        body.created_at,
+        student_id,
+        academic_term_id,
+        body.import_batch_id
     )
-        .fetch_one(&data.db)
+        .fetch_one(&mut *conn)
         .await
-        .map_err(|e| {
-            let error_response = json!({
-                "status": "fail",
-                "message": format!("Database error: {}", e),
-            });
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-        })?;
+        .map_err(|e| receipts::insert_error(receipt.as_ref(), e))?;
 
-    let record_response = json!({"status": "success", "data": json!({
-        "record": record
-    })});
+    log_event(&mut *conn, ctx, "create", "record", &record.id.to_string(), None, Some(snapshot(&record)))
+        .await
+        .map_err(db_error)?;
+    ledger::append(&mut *conn, "create", record.id, Some(&record))
+        .await
+        .map_err(db_error)?;
+    installments::rebuild_for(&mut *conn, &record).await?;
+
+    Ok(record)
+}
+
+pub fn create_candidate(body: &CreateRecordSchema) -> Candidate<'_> {
+    Candidate {
+        id: None,
+        student_id: body.student_id,
+        first_name: &body.first_name,
+        last_name: &body.last_name,
+        payment_for: &body.payment_for,
+        amount: &body.amount,
+        at: body.created_at,
+    }
+}
 
-    Ok(Json(record_response))
+// The `stage_*` functions run one create, patch or delete on a connection the caller
+// owns: the single-record handlers give each its own transaction, and batch requests
+// run every item in a savepoint of a shared one. Nothing is committed here, and a
+// `Pending` outcome means an approval rule turned the change into a change request.
+pub async fn stage_create(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    user: &User,
+    mut body: CreateRecordSchema,
+    duplicate_check: DuplicateCheck,
+) -> Result<Outcome<Record>, (StatusCode, Json<serde_json::Value>)> {
+    check_payment_date(&body)?;
+    catalogs::normalize_create(&mut *conn, &mut body).await?;
+    body.amount = payment_amount(&body.amount)?;
+    duplicates::check(&mut *conn, duplicate_check, &create_candidate(&body)).await?;
+
+    let rules = approvals::matching_rules(&mut *conn, user, &ProposedChange::Create { created_at: body.created_at }).await?;
+    if !rules.is_empty() {
+        let change_request = approvals::submit(&mut *conn, ctx, user, "create", None, snapshot(&body), &rules).await?;
+        return Ok(Outcome::Pending(Box::new(change_request)));
+    }
+
+    apply_create(conn, ctx, user, &body).await.map(Outcome::Applied)
+}
+
+// With an Idempotency-Key header, a retry of a request that went through gets the
+// original response back instead of creating a second payment. A suspected duplicate
+// is refused with a 409 until it is resent with `confirm_duplicate=true`.
+pub async fn create_record_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Query(params): Query<DuplicateQuery>,
+    headers: HeaderMap,
+    ctx: AuditContext,
+    Json(body): Json<CreateRecordSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let key = idempotency::key(&headers)?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    if let Some(key) = &key {
+        let request_hash = idempotency::request_hash("POST /api/records", &body);
+        if let Some(stored) = idempotency::claim(&mut tx, &user, key, &request_hash, data.env.idempotency_ttl_hours).await? {
+            return idempotency::replay(stored);
+        }
+    }
+
+    let duplicate_check = DuplicateCheck::new(&data.env, params.confirm_duplicate.unwrap_or(false));
+    let (status, record_response) = match stage_create(&mut tx, &ctx, &user, body, duplicate_check).await? {
+        Outcome::Applied(record) => (StatusCode::OK, json!({"status": "success", "data": json!({
+            "record": record
+        })})),
+        Outcome::Pending(change_request) => (StatusCode::ACCEPTED, approvals::pending_body(&change_request)),
+    };
+
+    if let Some(key) = &key {
+        idempotency::store(&mut tx, &user, key, status, &record_response).await?;
+    }
+
+    tx.commit().await.map_err(db_error)?;
+
+    Ok((status, Json(record_response)).into_response())
 }
 
 async fn fetch_all_records(
     db: &sqlx::PgPool,
+    filter: &RecordFilter,
 ) -> Result<Vec<Record>, (StatusCode, Json<serde_json::Value>)> {
-    sqlx::query_as!(
-        Record,
-        "SELECT * FROM \"records\""
-    )
+    let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM \"records\" WHERE deleted_at IS NULL");
+    terms::push_filter(&mut query, filter)?;
+
+    query
+        .build_query_as::<Record>()
         .fetch_all(db)
         .await
         .map_err(|e| {
@@ -63,9 +188,10 @@ async fn fetch_all_records(
 }
 
 pub async fn get_all_records(
-    State(data): State<Arc<AppState>>
+    State(data): State<Arc<AppState>>,
+    Query(filter): Query<RecordFilter>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let records: Vec<Record> = fetch_all_records(&data.db).await?;
+    let records: Vec<Record> = fetch_all_records(&data.db, &filter).await?;
 
     let json_response = json!({
         "status": "success",
@@ -77,66 +203,498 @@ pub async fn get_all_records(
     Ok(Json(json_response))
 }
 
-pub async fn update_record_handler(
+pub async fn get_record_handler(
     State(data): State<Arc<AppState>>,
-    Json(body): Json<UpdateRecordSchema>
+    Path(id): Path<i32>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     let record = sqlx::query_as!(
         Record,
-        "UPDATE \"records\" SET last_updated_by = $1, first_name = $2, last_name = $3, mi = $4, course = $5, year_level = $6, payment_for = $7, amount = $8, received_by = $9, updated_at = NOW() WHERE id = $10 RETURNING *",
-        body.last_updated_by.to_string(),
-        body.first_name.to_string(),
-        body.last_name.to_string(),
-        body.mi.to_string(),
-        body.course.to_string(),
-        body.year_level.to_string(),
-        body.payment_for.to_string(),
-        body.amount,
-        body.received_by.to_string(),
-        body.id
+        "SELECT * FROM \"records\" WHERE id = $1 AND deleted_at IS NULL",
+        id
     )
-        .fetch_one(&data.db)
+        .fetch_optional(&data.db)
         .await
-        .map_err(|e| {
-            let error_response = json!({
-                "status": "fail",
-                "message": format!("Database error: {}", e),
-            });
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-        })?;
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No record found with the provided ID"))?;
 
     let record_response = json!({"status": "success", "data": json!({
         "record": record
     })});
 
-    Ok(Json(record_response))
+    Ok(([(header::ETAG, etag(record.version))], Json(record_response)))
 }
 
-pub async fn delete_record_handler(
-    State(data): State<Arc<AppState>>,
-    Json(body): Json<DeleteRecordSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let rows_deleted = sqlx::query!(
-        "DELETE FROM \"records\" WHERE id = $1",
-        body.id
+// Loads a live record and holds its row lock until the surrounding transaction ends,
+// so the version check below and the write that follows cannot interleave with another writer.
+pub async fn lock_record(
+    conn: &mut PgConnection,
+    id: i32,
+) -> Result<Record, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        id
     )
-        .execute(&data.db)
+        .fetch_optional(conn)
         .await
-        .map_err(|e| {
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No record found with the provided ID"))
+}
+
+pub fn check_version(
+    current: &Record,
+    expected_version: Option<i32>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match expected_version {
+        Some(version) if version != current.version => {
             let error_response = json!({
+                "status": "fail",
+                "message": "Record has been modified since it was last fetched",
+                "data": json!({
+                    "version": current.version,
+                    "record": current
+                })
+            });
+            Err((StatusCode::PRECONDITION_FAILED, Json(error_response)))
+        }
+        _ => Ok(()),
+    }
+}
+
+const FINANCIAL_FIELDS: [&str; 3] = ["amount", "payment_for", "received_by"];
+
+const DUPLICATE_FIELDS: [&str; 5] = ["first_name", "last_name", "payment_for", "amount", "student_id"];
+
+fn column_value<'a>(record: &'a Record, column: &str) -> &'a str {
+    match column {
+        "last_updated_by" => &record.last_updated_by,
+        "first_name" => &record.first_name,
+        "last_name" => &record.last_name,
+        "mi" => &record.mi,
+        "course" => &record.course,
+        "year_level" => &record.year_level,
+        "payment_for" => &record.payment_for,
+        "amount" => &record.amount,
+        _ => &record.received_by,
+    }
+}
+
+type Assignments = Vec<(&'static str, String)>;
+
+// Column/value pairs a patch writes. The middle initial is the only optional part of
+// a name, so null clears it; any other null is rejected.
+fn patch_assignments(
+    body: &PatchRecordSchema,
+) -> Result<Assignments, (StatusCode, Json<serde_json::Value>)> {
+    let mut assignments = Vec::new();
+
+    for (column, value) in body.fields() {
+        let value = match value {
+            None => continue,
+            Some(Some(value)) => value.to_string(),
+            Some(None) if column == "mi" => String::new(),
+            Some(None) => {
+                let error_response = json!({
+                    "status": "fail",
+                    "message": format!("Field '{}' cannot be null", column),
+                });
+                return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+            }
+        };
+        assignments.push((column, value));
+    }
+
+    if assignments.is_empty() && body.student_id.is_none() && body.academic_term_id.is_none() {
+        let error_response = json!({
             "status": "fail",
-            "message": format!("Database error: {}", e),
+            "message": "No fields to update",
         });
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-        })?;
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+
+    Ok(assignments)
+}
 
-    if rows_deleted.rows_affected() == 0 {
+// Checks course, year level and payment category against their catalogs. Values the
+// record already has are left alone, so full PUTs of records written before an entry was
+// retired still go through.
+async fn check_catalogs(
+    conn: &mut PgConnection,
+    current: &Record,
+    assignments: &mut Assignments,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    for (column, value) in assignments.iter_mut() {
+        if value != column_value(current, column) {
+            *value = catalogs::canonical(&mut *conn, column, value).await?;
+        }
+    }
+    Ok(())
+}
+
+// Payments are positive and stored in the `format_cents` form; negative amounts only
+// come from voids and refunds.
+fn payment_amount(value: &str) -> Result<String, (StatusCode, Json<serde_json::Value>)> {
+    catalogs::normalize_amount("amount", value)
+        .ok()
+        .filter(|amount| amount != "0.00")
+        .ok_or_else(|| bad_request(&format!("amount must be a positive number, got {:?}", value)))
+}
+
+// Like `check_catalogs`, an amount the record already has is left alone.
+fn check_amount(
+    current: &Record,
+    assignments: &mut Assignments,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    for (column, value) in assignments.iter_mut() {
+        if *column == "amount" && *value != current.amount {
+            *value = payment_amount(value)?;
+        }
+    }
+    Ok(())
+}
+
+// Once a record is finalized its financial fields can only be corrected with a void or
+// refund. Resending the current value is allowed so full PUTs of other fields still work.
+fn check_finalized(
+    current: &Record,
+    assignments: &[(&str, String)],
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if current.finalized_at.is_none() {
+        return Ok(());
+    }
+
+    let blocked: Vec<&str> = assignments
+        .iter()
+        .filter(|(column, value)| FINANCIAL_FIELDS.contains(column) && value != column_value(current, column))
+        .map(|(column, _)| *column)
+        .collect();
+
+    if blocked.is_empty() {
+        return Ok(());
+    }
+
+    Err(conflict(&format!("Record is finalized; {} can only be corrected with a void or refund", blocked.join(", "))))
+}
+
+fn check_deletable(current: &Record) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if current.finalized_at.is_some() {
+        return Err(conflict("Finalized records cannot be deleted; void them instead"));
+    }
+    Ok(())
+}
+
+// Writes a patch to a record the caller has locked and version checked. Shared by the
+// update routes and by approved change requests.
+pub async fn apply_patch(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    before: &Record,
+    body: &PatchRecordSchema,
+) -> Result<Record, (StatusCode, Json<serde_json::Value>)> {
+    let mut assignments = patch_assignments(body)?;
+    check_catalogs(&mut *conn, before, &mut assignments).await?;
+    check_amount(before, &mut assignments)?;
+    check_finalized(before, &assignments)?;
+    if let Some(Some(student_id)) = body.student_id {
+        students::check_student(&mut *conn, student_id).await?;
+    }
+    if let Some(Some(academic_term_id)) = body.academic_term_id {
+        terms::check_term(&mut *conn, academic_term_id).await?;
+    }
+
+    let mut query = QueryBuilder::<Postgres>::new("UPDATE \"records\" SET ");
+    let mut separated = query.separated(", ");
+    for (column, value) in assignments {
+        separated.push(format!("{} = ", column));
+        separated.push_bind_unseparated(value);
+    }
+    if let Some(student_id) = body.student_id {
+        separated.push("student_id = ");
+        separated.push_bind_unseparated(student_id);
+    }
+    if let Some(academic_term_id) = body.academic_term_id {
+        separated.push("academic_term_id = ");
+        separated.push_bind_unseparated(academic_term_id);
+    }
+    separated.push("updated_at = NOW()");
+    separated.push("version = version + 1");
+    query.push(" WHERE id = ").push_bind(before.id).push(" RETURNING *");
+
+    let record = query
+        .build_query_as::<Record>()
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut *conn, ctx, "update", "record", &record.id.to_string(), Some(snapshot(before)), Some(snapshot(&record)))
+        .await
+        .map_err(db_error)?;
+    ledger::append(&mut *conn, "update", record.id, Some(&record))
+        .await
+        .map_err(db_error)?;
+    if (before.student_id, &before.payment_for) != (record.student_id, &record.payment_for) {
+        installments::rebuild_for(&mut *conn, before).await?;
+    }
+    installments::rebuild_for(&mut *conn, &record).await?;
+
+    Ok(record)
+}
+
+// Approval rules and the duplicate check only look at the fields whose values
+// actually change, so resending a record as it is never needs approval.
+pub async fn stage_patch(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    user: &User,
+    id: i32,
+    expected_version: Option<i32>,
+    body: &PatchRecordSchema,
+    duplicate_check: DuplicateCheck,
+) -> Result<Outcome<Record>, (StatusCode, Json<serde_json::Value>)> {
+    let mut assignments = patch_assignments(body)?;
+
+    let before = lock_record(&mut *conn, id).await?;
+    check_version(&before, expected_version)?;
+    check_catalogs(&mut *conn, &before, &mut assignments).await?;
+    check_amount(&before, &mut assignments)?;
+    check_finalized(&before, &assignments)?;
+
+    let mut changed: Vec<&str> = assignments
+        .iter()
+        .filter(|(column, value)| value != column_value(&before, column))
+        .map(|(column, _)| *column)
+        .collect();
+    if body.student_id.is_some_and(|student_id| student_id != before.student_id) {
+        changed.push("student_id");
+    }
+    if body.academic_term_id.is_some_and(|academic_term_id| academic_term_id != before.academic_term_id) {
+        changed.push("academic_term_id");
+    }
+
+    // Only changes that could make the record match another payment are checked, so
+    // editing other fields of a confirmed duplicate does not warn again.
+    if before.entry_type == "payment" && changed.iter().any(|column| DUPLICATE_FIELDS.contains(column)) {
+        let value = |column: &str| {
+            assignments
+                .iter()
+                .find(|(assigned, _)| *assigned == column)
+                .map(|(_, value)| value.as_str())
+                .unwrap_or_else(|| column_value(&before, column))
+        };
+        let candidate = Candidate {
+            id: Some(before.id),
+            student_id: body.student_id.unwrap_or(before.student_id),
+            first_name: value("first_name"),
+            last_name: value("last_name"),
+            payment_for: value("payment_for"),
+            amount: value("amount"),
+            at: before.created_at,
+        };
+        duplicates::check(&mut *conn, duplicate_check, &candidate).await?;
+    }
+    let rules = approvals::matching_rules(&mut *conn, user, &ProposedChange::Update { fields: &changed }).await?;
+    if !rules.is_empty() {
+        let change_request = approvals::submit(&mut *conn, ctx, user, "update", Some(&before), snapshot(body), &rules).await?;
+        return Ok(Outcome::Pending(Box::new(change_request)));
+    }
+
+    apply_patch(conn, ctx, &before, body).await.map(Outcome::Applied)
+}
+
+async fn patch_record(
+    db: &sqlx::PgPool,
+    ctx: &AuditContext,
+    user: &User,
+    id: i32,
+    expected_version: Option<i32>,
+    body: &PatchRecordSchema,
+    duplicate_check: DuplicateCheck,
+) -> Result<Outcome<Record>, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = db.begin().await.map_err(db_error)?;
+
+    let outcome = stage_patch(&mut tx, ctx, user, id, expected_version, body, duplicate_check).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(outcome)
+}
+
+fn updated_response(outcome: Outcome<Record>) -> Response<Body> {
+    match outcome {
+        Outcome::Applied(record) => {
+            let record_response = json!({"status": "success", "data": json!({
+                "record": record
+            })});
+            ([(header::ETAG, etag(record.version))], Json(record_response)).into_response()
+        }
+        Outcome::Pending(change_request) => approvals::pending_response(&change_request),
+    }
+}
+
+pub async fn update_record_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Query(params): Query<DuplicateQuery>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    ctx: AuditContext,
+    Json(body): Json<UpdateRecordSchema>
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let expected_version = if_match(&headers)?.required()?;
+    let duplicate_check = DuplicateCheck::new(&data.env, params.confirm_duplicate.unwrap_or(false));
+    let outcome = patch_record(&data.db, &ctx, &user, id, expected_version, &PatchRecordSchema::from(&body), duplicate_check).await?;
+
+    Ok(updated_response(outcome))
+}
+
+pub async fn patch_record_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Query(params): Query<DuplicateQuery>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    ctx: AuditContext,
+    Json(body): Json<PatchRecordSchema>
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let expected_version = if_match(&headers)?.required()?;
+    let duplicate_check = DuplicateCheck::new(&data.env, params.confirm_duplicate.unwrap_or(false));
+    let outcome = patch_record(&data.db, &ctx, &user, id, expected_version, &body, duplicate_check).await?;
+
+    Ok(updated_response(outcome))
+}
+
+pub async fn legacy_update_record_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Query(params): Query<DuplicateQuery>,
+    headers: HeaderMap,
+    ctx: AuditContext,
+    Json(body): Json<LegacyUpdateRecordSchema>
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let expected_version = if_match(&headers)?.optional();
+    let duplicate_check = DuplicateCheck::new(&data.env, params.confirm_duplicate.unwrap_or(false));
+    let outcome = patch_record(&data.db, &ctx, &user, body.id, expected_version, &PatchRecordSchema::from(&body.record), duplicate_check).await?;
+
+    Ok(updated_response(outcome))
+}
+
+// Soft deletes a record the caller has locked and version checked.
+pub async fn apply_delete(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    before: &Record,
+    deleted_by: &str,
+    reason: Option<&str>,
+) -> Result<Record, (StatusCode, Json<serde_json::Value>)> {
+    check_deletable(before)?;
+
+    let record = sqlx::query_as!(
+        Record,
+        "UPDATE \"records\" SET deleted_at = NOW(), deleted_by = $1, delete_reason = $2, version = version + 1 WHERE id = $3 RETURNING *",
+        deleted_by,
+        reason,
+        before.id
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut *conn, ctx, "delete", "record", &record.id.to_string(), Some(snapshot(before)), Some(snapshot(&record)))
+        .await
+        .map_err(db_error)?;
+    ledger::append(&mut *conn, "delete", record.id, Some(&record))
+        .await
+        .map_err(db_error)?;
+    installments::rebuild_for(&mut *conn, &record).await?;
+
+    Ok(record)
+}
+
+// Moves a record to the trash. Non-admins can only delete through a matching approval
+// rule, which turns the delete into a change request for an approver.
+pub async fn stage_delete(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    user: &User,
+    id: i32,
+    expected_version: Option<i32>,
+    reason: Option<&str>,
+) -> Result<Outcome<Record>, (StatusCode, Json<serde_json::Value>)> {
+    let before = lock_record(&mut *conn, id).await?;
+    check_version(&before, expected_version)?;
+    check_deletable(&before)?;
+
+    let rules = approvals::matching_rules(&mut *conn, user, &ProposedChange::Delete).await?;
+    if !rules.is_empty() {
+        let change_request = approvals::submit(&mut *conn, ctx, user, "delete", Some(&before), json!({"reason": reason}), &rules).await?;
+        return Ok(Outcome::Pending(Box::new(change_request)));
+    }
+    if user.role != "admin" {
+        return Err(forbidden("Only admins can delete records unless an approval rule covers the delete"));
+    }
+
+    apply_delete(conn, ctx, &before, &user.username, reason).await.map(Outcome::Applied)
+}
+
+async fn delete_record(
+    db: &sqlx::PgPool,
+    ctx: &AuditContext,
+    user: &User,
+    id: i32,
+    expected_version: Option<i32>,
+    reason: Option<&str>,
+) -> Result<Outcome<Record>, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = db.begin().await.map_err(db_error)?;
+
+    let outcome = stage_delete(&mut tx, ctx, user, id, expected_version, reason).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(outcome)
+}
+
+pub async fn delete_record_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+    Query(params): Query<DeleteRecordQuery>,
+    headers: HeaderMap,
+    ctx: AuditContext,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let expected_version = if_match(&headers)?.required()?;
+
+    let reason = params.reason.as_deref().map(str::trim).unwrap_or_default();
+    if reason.is_empty() {
         let error_response = json!({
             "status": "fail",
-            "message": "No record found with the provided ID"
+            "message": "A reason is required to delete a record",
         });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
 
-        return Err((StatusCode::NOT_FOUND, Json(error_response)))
+    if let Outcome::Pending(change_request) = delete_record(&data.db, &ctx, &user, id, expected_version, Some(reason)).await? {
+        return Ok(approvals::pending_response(&change_request));
+    }
+
+    let response = json!({
+        "status": "success",
+        "message": "Record moved to trash"
+    });
+
+    Ok(Json(response).into_response())
+}
+
+pub async fn legacy_delete_record_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    headers: HeaderMap,
+    ctx: AuditContext,
+    Json(body): Json<DeleteRecordSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let expected_version = if_match(&headers)?.optional();
+
+    if let Outcome::Pending(change_request) = delete_record(&data.db, &ctx, &user, body.id, expected_version, body.reason.as_deref()).await? {
+        return Ok(approvals::pending_response(&change_request));
     }
 
     let response = json!({
@@ -144,10 +702,128 @@ pub async fn delete_record_handler(
         "message": "Record Successfully Deleted"
     });
 
+    Ok(Json(response).into_response())
+}
+
+pub async fn get_trashed_records(
+    State(data): State<Arc<AppState>>
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let records: Vec<Record> = sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "records": records
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+async fn lock_trashed_record(
+    conn: &mut PgConnection,
+    id: i32,
+) -> Result<Record, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE id = $1 AND deleted_at IS NOT NULL FOR UPDATE",
+        id
+    )
+        .fetch_optional(conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No deleted record found with the provided ID"))
+}
+
+pub async fn restore_record_handler(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_trashed_record(&mut tx, id).await?;
+
+    let record = sqlx::query_as!(
+        Record,
+        "UPDATE \"records\" SET deleted_at = NULL, deleted_by = NULL, delete_reason = NULL, updated_at = NOW(), version = version + 1 WHERE id = $1 RETURNING *",
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "restore", "record", &id.to_string(), Some(snapshot(&before)), Some(snapshot(&record)))
+        .await
+        .map_err(db_error)?;
+    ledger::append(&mut tx, "restore", id, Some(&record))
+        .await
+        .map_err(db_error)?;
+    installments::rebuild_for(&mut tx, &record).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let record_response = json!({"status": "success", "data": json!({
+        "record": record
+    })});
+
+    Ok(([(header::ETAG, etag(record.version))], Json(record_response)))
+}
+
+pub async fn purge_record_handler(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_trashed_record(&mut tx, id).await?;
+
+    let deleted_at = before.deleted_at.unwrap_or_else(chrono::Utc::now);
+    let purgeable_at = deleted_at + chrono::Duration::days(data.env.trash_retention_days);
+    if purgeable_at > chrono::Utc::now() {
+        let error_response = json!({
+            "status": "fail",
+            "message": format!("Deleted records are kept for {} days before they can be purged", data.env.trash_retention_days),
+            "data": json!({
+                "purgeableAt": purgeable_at
+            })
+        });
+        return Err((StatusCode::CONFLICT, Json(error_response)));
+    }
+
+    sqlx::query!(
+        "DELETE FROM \"records\" WHERE id = $1",
+        id
+    )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "purge", "record", &id.to_string(), Some(snapshot(&before)), None)
+        .await
+        .map_err(db_error)?;
+    ledger::append(&mut tx, "purge", id, None)
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let response = json!({
+        "status": "success",
+        "message": "Record permanently deleted"
+    });
+
     Ok(Json(response))
 }
 
-fn handle_xlsx_error<T>(result: Result<T, xlsxwriter::XlsxError>) -> Result<T, (StatusCode, Json<serde_json::Value>)> {
+pub fn handle_xlsx_error<T>(result: Result<T, xlsxwriter::XlsxError>) -> Result<T, (StatusCode, Json<serde_json::Value>)> {
     match result {
         Ok(val) => Ok(val),
         Err(e) => {
@@ -155,96 +831,495 @@ fn handle_xlsx_error<T>(result: Result<T, xlsxwriter::XlsxError>) -> Result<T, (
                 "status": "fail",
                 "message": format!("Excel error: {}", e),
             });
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)))
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)))
         }
     }
 }
 
-pub async fn create_excel_all_record(
-    State(data): State<Arc<AppState>>
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let records: Vec<Record> = fetch_all_records(&data.db).await?;
+const SUMMARY_GROUPS: [&str; 2] = ["course", "payment_for"];
 
-    let wb = handle_xlsx_error(xlsxwriter::Workbook::new("/tmp/records.xlsx"))?;
+fn write_record_cell(
+    sheet: &mut xlsxwriter::Worksheet,
+    formats: &xlsx::Formats,
+    row: u32,
+    col: u16,
+    record: &Record,
+    key: &str,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match key {
+        "id" => handle_xlsx_error(sheet.write_number(row, col, record.id.into(), None)),
+        "created_at" | "updated_at" | "finalized_at" => {
+            xlsx::write_datetime(sheet, row, col, record_columns::timestamp(record, key), &formats.datetime)
+        }
+        "student_id" | "academic_term_id" | "reverses_record_id" => match record_columns::link(record, key) {
+            Some(id) => handle_xlsx_error(sheet.write_number(row, col, id.into(), None)),
+            None => handle_xlsx_error(sheet.write_blank(row, col, None)),
+        },
+        "amount" => xlsx::write_money(sheet, row, col, &record.amount, &formats.money),
+        _ => handle_xlsx_error(sheet.write_string(row, col, &record_columns::text(record, key), None)),
+    }
+}
 
-    let mut sheet = handle_xlsx_error(wb.add_worksheet(None))?;
+// A sheet of the selected columns of `records`, with the amounts totalled underneath
+// when the amount column is among them.
+fn write_records_sheet(
+    sheet: &mut xlsxwriter::Worksheet,
+    formats: &xlsx::Formats,
+    records: &[&Record],
+    fields: &[&str],
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let columns: Vec<xlsx::Column> = fields.iter().map(|key| record_columns::header(key)).collect();
+    xlsx::write_header(sheet, formats, &columns)?;
 
-    let headers = &["Id", "Created At", "Updated At", "Last Updated By", "First Name", "Last Name", "MI", "Course", "Year Level", "Payment For", "Amount", "Received By"];
+    let mut total_cents = 0;
+    for (i, record) in records.iter().enumerate() {
+        let row = (i + 1) as u32; // Start writing data from the second row
+        for (col, key) in fields.iter().enumerate() {
+            write_record_cell(sheet, formats, row, col as u16, record, key)?;
+        }
+        total_cents += amount::parse_cents(&record.amount).unwrap_or(0);
+    }
+    xlsx::autofilter(sheet, &columns, records.len())?;
 
-    for (i, header) in headers.iter().enumerate() {
-        sheet.write_string(0, i as u16, header, None)
-            .map_err(|e| {
-                let error_response = json!({
-                    "status": "fail",
-                    "message": format!("Excel error: {}", e)
-                });
+    let Some(amount_col) = fields.iter().position(|key| *key == "amount") else {
+        return Ok(());
+    };
+    let totals = (records.len() + 1) as u32;
+    if amount_col > 0 {
+        handle_xlsx_error(sheet.write_string(totals, 0, "Total", Some(&formats.total_label)))?;
+    }
+    xlsx::write_sum(sheet, formats, totals, amount_col as u16, records.len(), total_cents)
+}
+
+fn write_records_workbook(
+    path: &str,
+    records: &[Record],
+    fields: &[&str],
+    properties: &xlsx::Properties,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let wb = handle_xlsx_error(xlsxwriter::Workbook::new(path))?;
+    let formats = xlsx::Formats::new();
 
-                (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-            })?;
+    let mut sheet = handle_xlsx_error(wb.add_worksheet(Some("Records")))?;
+    let records: Vec<&Record> = records.iter().collect();
+    write_records_sheet(&mut sheet, &formats, &records, fields)?;
+
+    xlsx::write_properties(&wb, &formats, properties)?;
+
+    handle_xlsx_error(wb.close())
+}
+
+// Count and cents of a group of records. Amounts that do not parse count but add nothing.
+#[derive(Default, Clone, Copy)]
+struct Tally {
+    count: i64,
+    cents: i64,
+}
+
+impl Tally {
+    fn add(&mut self, record: &Record) {
+        self.count += 1;
+        self.cents += amount::parse_cents(&record.amount).unwrap_or(0);
     }
+}
 
-    for (i, record) in records.iter().enumerate() {
-        let row = (i + 1) as u32; // Start writing data from the second row
+// Writes `label` above a small table and returns the row after it.
+fn write_table_title(
+    sheet: &mut xlsxwriter::Worksheet,
+    formats: &xlsx::Formats,
+    row: u32,
+    label: &str,
+) -> Result<u32, (StatusCode, Json<serde_json::Value>)> {
+    handle_xlsx_error(sheet.write_string(row, 0, label, Some(&formats.total_label)))?;
+    Ok(row + 1)
+}
 
-        let mut write_to_sheet = |col: u16, data: &str| -> Result<(), (StatusCode, Json<serde_json::Value>)> {
-            sheet.write_string(row, col, data, None).map_err(|e| {
-                let error_response = json!({
-                "status": "fail",
-                "message": format!("Excel error: {}", e),
-            });
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-            })
+// Course × year level rows against payment category columns, with totals both ways.
+// `value` picks the count or the amount out of each tally.
+fn write_pivot(
+    sheet: &mut xlsxwriter::Worksheet,
+    formats: &xlsx::Formats,
+    start: u32,
+    pivot: &BTreeMap<(&str, &str), BTreeMap<&str, Tally>>,
+    categories: &[&str],
+    money: bool,
+) -> Result<u32, (StatusCode, Json<serde_json::Value>)> {
+    let (cell_format, total_format) = if money {
+        (Some(&formats.money), Some(&formats.total_money))
+    } else {
+        (None, Some(&formats.total_label))
+    };
+    let value = |tally: &Tally| if money { tally.cents as f64 / 100.0 } else { tally.count as f64 };
+    let total_col = (categories.len() + 2) as u16;
+
+    handle_xlsx_error(sheet.write_string(start, 0, "Course", Some(&formats.header)))?;
+    handle_xlsx_error(sheet.write_string(start, 1, "Year Level", Some(&formats.header)))?;
+    for (i, category) in categories.iter().enumerate() {
+        handle_xlsx_error(sheet.write_string(start, (i + 2) as u16, category, Some(&formats.header)))?;
+    }
+    handle_xlsx_error(sheet.write_string(start, total_col, "Total", Some(&formats.header)))?;
+
+    let first = start + 1;
+    let mut column_totals = vec![Tally::default(); categories.len() + 1];
+    for (i, ((course, year_level), cells)) in pivot.iter().enumerate() {
+        let row = first + i as u32;
+        handle_xlsx_error(sheet.write_string(row, 0, course, None))?;
+        handle_xlsx_error(sheet.write_string(row, 1, year_level, None))?;
+        let mut row_total = Tally::default();
+        for (j, category) in categories.iter().enumerate() {
+            let tally = cells.get(category).copied().unwrap_or_default();
+            handle_xlsx_error(sheet.write_number(row, (j + 2) as u16, value(&tally), cell_format))?;
+            row_total.count += tally.count;
+            row_total.cents += tally.cents;
+            column_totals[j].count += tally.count;
+            column_totals[j].cents += tally.cents;
+        }
+        let formula = format!("=SUM({}:{})", xlsx::cell(row, 2), xlsx::cell(row, total_col - 1));
+        handle_xlsx_error(sheet.write_formula_num(row, total_col, &formula, total_format, value(&row_total)))?;
+        column_totals[categories.len()].count += row_total.count;
+        column_totals[categories.len()].cents += row_total.cents;
+    }
+
+    let totals = first + pivot.len() as u32;
+    handle_xlsx_error(sheet.write_string(totals, 0, "Total", Some(&formats.total_label)))?;
+    for (j, tally) in column_totals.iter().enumerate() {
+        let col = (j + 2) as u16;
+        let formula = if pivot.is_empty() {
+            "=0".to_string()
+        } else {
+            format!("=SUM({}:{})", xlsx::cell(first, col), xlsx::cell(totals - 1, col))
         };
+        handle_xlsx_error(sheet.write_formula_num(totals, col, &formula, total_format, value(tally)))?;
+    }
 
-        write_to_sheet(0, &record.id.to_string())?;
-        write_to_sheet(1, &record.created_at.unwrap().to_string())?;
-        write_to_sheet(2, &record.updated_at.unwrap().to_string())?;
-        write_to_sheet(3, &record.last_updated_by)?;
-        write_to_sheet(4, &record.first_name)?;
-        write_to_sheet(5, &record.last_name)?;
-        write_to_sheet(6, &record.mi)?;
-        write_to_sheet(7, &record.course)?;
-        write_to_sheet(8, &record.year_level)?;
-        write_to_sheet(9, &record.payment_for)?;
-        write_to_sheet(10, &record.amount)?;
-        write_to_sheet(11, &record.received_by)?;
-    }
-
-    let close_result = wb.close();
-    if let Err(e) = close_result {
-        let error_response = json!({
-            "status": "fail",
-            "message": format!("Excel error: {}", e),
-        });
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)));
+    Ok(totals + 1)
+}
+
+// A summary sheet of subtotals and course × year level × payment category tables,
+// then one sheet of records per value of `group_by`.
+fn write_summary_workbook(
+    path: &str,
+    records: &[Record],
+    group_by: &str,
+    fields: &[&str],
+    properties: &xlsx::Properties,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let group_of = |record: &Record| -> String {
+        if group_by == "payment_for" {
+            record.payment_for.to_owned()
+        } else {
+            record.course.to_owned()
+        }
+    };
+    let group_label = if group_by == "payment_for" { "Payment For" } else { "Course" };
+
+    let mut groups: BTreeMap<String, Vec<&Record>> = BTreeMap::new();
+    let mut pivot: BTreeMap<(&str, &str), BTreeMap<&str, Tally>> = BTreeMap::new();
+    let mut categories: Vec<&str> = Vec::new();
+    for record in records {
+        groups.entry(group_of(record)).or_default().push(record);
+        pivot
+            .entry((record.course.as_str(), record.year_level.as_str()))
+            .or_default()
+            .entry(record.payment_for.as_str())
+            .or_default()
+            .add(record);
+        if !categories.contains(&record.payment_for.as_str()) {
+            categories.push(&record.payment_for);
+        }
+    }
+    categories.sort_unstable();
+
+    let wb = handle_xlsx_error(xlsxwriter::Workbook::new(path))?;
+    let formats = xlsx::Formats::new();
+
+    let mut sheet = handle_xlsx_error(wb.add_worksheet(Some("Summary")))?;
+    handle_xlsx_error(sheet.set_column(0, 1, 18.0, None))?;
+    handle_xlsx_error(sheet.set_column(2, (categories.len() + 2) as u16, 14.0, None))?;
+
+    let mut row = write_table_title(&mut sheet, &formats, 0, &format!("Totals by {}", group_label))?;
+    handle_xlsx_error(sheet.write_string(row, 0, group_label, Some(&formats.header)))?;
+    handle_xlsx_error(sheet.write_string(row, 1, "Records", Some(&formats.header)))?;
+    handle_xlsx_error(sheet.write_string(row, 2, "Amount", Some(&formats.header)))?;
+    let first = row + 1;
+    let mut total = Tally::default();
+    for (i, (group, members)) in groups.iter().enumerate() {
+        let line = first + i as u32;
+        let mut tally = Tally::default();
+        for record in members {
+            tally.add(record);
+        }
+        handle_xlsx_error(sheet.write_string(line, 0, group, None))?;
+        handle_xlsx_error(sheet.write_number(line, 1, tally.count as f64, None))?;
+        handle_xlsx_error(sheet.write_number(line, 2, tally.cents as f64 / 100.0, Some(&formats.money)))?;
+        total.count += tally.count;
+        total.cents += tally.cents;
+    }
+    row = first + groups.len() as u32;
+    handle_xlsx_error(sheet.write_string(row, 0, "Total", Some(&formats.total_label)))?;
+    let (count_formula, sum_formula) = if groups.is_empty() {
+        ("=0".to_string(), "=0".to_string())
+    } else {
+        (
+            format!("=SUM({}:{})", xlsx::cell(first, 1), xlsx::cell(row - 1, 1)),
+            format!("=SUM({}:{})", xlsx::cell(first, 2), xlsx::cell(row - 1, 2)),
+        )
+    };
+    handle_xlsx_error(sheet.write_formula_num(row, 1, &count_formula, Some(&formats.total_label), total.count as f64))?;
+    handle_xlsx_error(sheet.write_formula_num(row, 2, &sum_formula, Some(&formats.total_money), total.cents as f64 / 100.0))?;
+
+    row = write_table_title(&mut sheet, &formats, row + 2, "Amount by course, year level and payment category")?;
+    row = write_pivot(&mut sheet, &formats, row, &pivot, &categories, true)?;
+    row = write_table_title(&mut sheet, &formats, row + 1, "Records by course, year level and payment category")?;
+    write_pivot(&mut sheet, &formats, row, &pivot, &categories, false)?;
+
+    let mut taken = vec!["Summary".to_string(), "Properties".to_string()];
+    for (group, members) in &groups {
+        let name = xlsx::sheet_name(group, &taken);
+        let mut sheet = handle_xlsx_error(wb.add_worksheet(Some(&name)))?;
+        write_records_sheet(&mut sheet, &formats, members, fields)?;
+        taken.push(name);
+    }
+
+    xlsx::write_properties(&wb, &formats, properties)?;
+
+    handle_xlsx_error(wb.close())
+}
+
+fn records_properties<'a>(title: &'a str, user: &'a User, filter: &RecordFilter, fields: &[&str]) -> xlsx::Properties<'a> {
+    let mut filters = filter.describe();
+    if fields.len() < record_columns::RECORD_FIELDS.len() {
+        filters.push(format!("columns = {}", fields.join(",")));
+    }
+    xlsx::Properties {
+        title,
+        author: &user.name,
+        generated_by: &user.username,
+        filters,
+    }
+}
+
+// Writes the export to a file of its own and hands back the job to download it from.
+pub async fn create_excel_all_record(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Query(filter): Query<RecordFilter>,
+    Query(params): Query<ExportColumnsQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let fields = record_columns::select(params.columns.as_deref())?;
+    let records: Vec<Record> = fetch_all_records(&data.db, &filter).await?;
+
+    let file = exports::prepare(&data.env, "xlsx").await?;
+    let properties = records_properties("Payment records", &user, &filter, &fields);
+    let export = match write_records_workbook(&file.path.to_string_lossy(), &records, &fields, &properties) {
+        Ok(()) => exports::register(&data.db, &data.env, &user, &file, "records", "records.xlsx", records.len()).await,
+        Err(e) => Err(e),
+    };
+    let export = match export {
+        Ok(export) => export,
+        Err(e) => {
+            exports::discard(&file).await;
+            return Err(e);
+        }
     };
 
     let response = json!({
         "status": "success",
-        "message": "successfully created excel file"
+        "message": "successfully created excel file",
+        "data": json!({
+            "export": export,
+            "download_url": format!("/api/exports/{}/download", export.id)
+        })
     });
 
     Ok(Json(response))
 }
 
-pub async fn download_excel_handler() -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let file = File::open("/tmp/records.xlsx").await.map_err(|e| {
-        let error_response = json!({
-            "status": "fail",
-            "message": format!("File error: {}", e),
-        });
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-    })?;
+// One-call export: the workbook is written to a scratch file and streamed back, with
+// nothing kept on the server.
+pub async fn export_records_xlsx_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Query(filter): Query<RecordFilter>,
+    Query(params): Query<ExportColumnsQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let fields = record_columns::select(params.columns.as_deref())?;
+    let records: Vec<Record> = fetch_all_records(&data.db, &filter).await?;
+
+    let path = exports::temp_path("xlsx");
+    let properties = records_properties("Payment records", &user, &filter, &fields);
+    if let Err(e) = write_records_workbook(&path.to_string_lossy(), &records, &fields, &properties) {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(e);
+    }
+
+    let filename = format!("records-{}.xlsx", Utc::now().format("%Y%m%d-%H%M%S"));
+    exports::stream_temp(&path, &filename).await
+}
+
+// Same filters as the listing, plus `group_by` (course or payment_for) to pick what
+// gets a sheet of its own.
+pub async fn export_records_summary_xlsx_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Query(params): Query<SummaryExportQuery>,
+    Query(filter): Query<RecordFilter>,
+    Query(columns): Query<ExportColumnsQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let group_by = params.group_by.unwrap_or_else(|| "course".to_string());
+    if !SUMMARY_GROUPS.contains(&group_by.as_str()) {
+        return Err(bad_request(&format!("Invalid group_by: {}. Expected one of: {}", group_by, SUMMARY_GROUPS.join(", "))));
+    }
+
+    let fields = record_columns::select(columns.columns.as_deref())?;
+    let records: Vec<Record> = fetch_all_records(&data.db, &filter).await?;
+
+    let mut properties = records_properties("Payment records summary", &user, &filter, &fields);
+    properties.filters.push(format!("group_by = {}", group_by));
+    let path = exports::temp_path("xlsx");
+    if let Err(e) = write_summary_workbook(&path.to_string_lossy(), &records, &group_by, &fields, &properties) {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(e);
+    }
+
+    let filename = format!("records-summary-by-{}-{}.xlsx", group_by.replace('_', "-"), Utc::now().format("%Y%m%d-%H%M%S"));
+    exports::stream_temp(&path, &filename).await
+}
+
+// Rows are read from the database as a stream and encoded one at a time into the
+// response, so an export of any size holds a single record in memory. The query runs
+// in a task of its own because the response body has to outlive the handler; a database
+// error after the first bytes are sent can only cut the download short.
+fn stream_records<F>(
+    db: sqlx::PgPool,
+    mut query: QueryBuilder<'static, Postgres>,
+    first: Option<String>,
+    encode: F,
+) -> Body
+where
+    F: Fn(&Record) -> String + Send + 'static,
+{
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<String, sqlx::Error>>(64);
+    tokio::spawn(async move {
+        if let Some(first) = first {
+            if tx.send(Ok(first)).await.is_err() {
+                return;
+            }
+        }
+        let mut rows = query.build_query_as::<Record>().fetch(&db);
+        while let Some(row) = rows.next().await {
+            let chunk = row.map(|record| encode(&record));
+            let failed = chunk.is_err();
+            // The client hung up, or the query failed; either way stop reading.
+            if tx.send(chunk).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    Body::from_stream(futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    }))
+}
 
+fn export_query(filter: &RecordFilter) -> Result<QueryBuilder<'static, Postgres>, (StatusCode, Json<serde_json::Value>)> {
+    let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM \"records\" WHERE deleted_at IS NULL");
+    terms::push_filter(&mut query, filter)?;
+    query.push(" ORDER BY id");
+    Ok(query)
+}
+
+fn download_response(body: Body, content_type: &str, filename: &str) -> Response<Body> {
+    Response::builder()
+        .header("Content-Type", content_type)
+        .header("Content-Disposition", format!("attachment; filename={}", filename))
+        .body(body)
+        .unwrap()
+}
+
+// RFC 4180 CSV with a header row of column keys. Same filters and `columns` as the
+// Excel export.
+pub async fn export_records_csv_handler(
+    State(data): State<Arc<AppState>>,
+    Query(filter): Query<RecordFilter>,
+    Query(columns): Query<ExportColumnsQuery>,
+    Query(params): Query<CsvExportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let fields = record_columns::select(columns.columns.as_deref())?;
+    let delimiter = record_columns::delimiter(params.delimiter.as_deref())?;
+    let query = export_query(&filter)?;
+
+    let header = record_columns::csv_header(&fields, delimiter, params.bom.unwrap_or(false));
+
+    let body = stream_records(data.db.clone(), query, Some(header), move |record| {
+        record_columns::csv_record(record, &fields, delimiter)
+    });
+    let filename = format!("records-{}.csv", Utc::now().format("%Y%m%d-%H%M%S"));
+    Ok(download_response(body, "text/csv; charset=utf-8", &filename))
+}
+
+// One JSON object per line, keyed by column.
+pub async fn export_records_ndjson_handler(
+    State(data): State<Arc<AppState>>,
+    Query(filter): Query<RecordFilter>,
+    Query(columns): Query<ExportColumnsQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let fields = record_columns::select(columns.columns.as_deref())?;
+    let query = export_query(&filter)?;
+
+    let body = stream_records(data.db.clone(), query, None, move |record| {
+        record_columns::ndjson_record(record, &fields)
+    });
+    let filename = format!("records-{}.ndjson", Utc::now().format("%Y%m%d-%H%M%S"));
+    Ok(download_response(body, "application/x-ndjson", &filename))
+}
+
+// Kept for clients written against the old shared file: serves the caller's own most
+// recent records export.
+pub async fn download_excel_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let export = sqlx::query_as!(
+        ExportJob,
+        "SELECT * FROM \"export_jobs\" WHERE owner_id = $1 AND kind = 'records' ORDER BY created_at DESC LIMIT 1",
+        user.id
+    )
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No records export found; create one first"))?;
+
+    exports::download(&export).await
+}
+
+// Streams a workbook from disk as a download.
+pub fn xlsx_response(file: File, filename: &str) -> Response<Body> {
     let stream = FramedRead::new(file, BytesCodec::new());
 
     let body = Body::from_stream(stream);
 
-    let response = Response::builder()
+    Response::builder()
         .header("Content-Type", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
-        .header("Content-Disposition", "attachment; filename=records.xlsx")
+        .header("Content-Disposition", format!("attachment; filename={}", filename))
         .body(body)
-        .unwrap();
+        .unwrap()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payment_amounts_are_stored_in_cents_form() {
+        assert_eq!(payment_amount("1500").unwrap(), "1500.00");
+        assert_eq!(payment_amount(" 1,500.5 ").unwrap(), "1500.50");
+    }
 
-    Ok(response)
-}
\ No newline at end of file
+    #[test]
+    fn rejects_negative_zero_and_non_numeric_amounts() {
+        for value in ["-1500", "0", "0.00", "1,5OO", "PHP 100", ""] {
+            let (status, _) = payment_amount(value).unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{:?}", value);
+        }
+    }
+}
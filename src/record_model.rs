@@ -1,5 +1,5 @@
 use chrono::prelude::*;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
 
 #[allow(non_snake_case)]
@@ -19,8 +19,29 @@ pub struct Record {
     pub payment_for: String,
     pub amount: String,
     pub received_by: String,
+    pub version: i32,
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<String>,
+    pub delete_reason: Option<String>,
+    pub or_number: Option<String>,
+    pub or_series_id: Option<i32>,
+    pub or_scope_key: Option<String>,
+    pub or_year: Option<i32>,
+    pub or_sequence: Option<i64>,
+    // "payment", or "void"/"refund" for reversing entries linked through `reverses_record_id`.
+    pub entry_type: String,
+    pub reverses_record_id: Option<i32>,
+    pub reversal_reason: Option<String>,
+    pub approved_by: Option<String>,
+    #[serde(rename = "finalizedAt")]
+    pub finalized_at: Option<DateTime<Utc>>,
+    pub finalized_by: Option<String>,
+    pub student_id: Option<i32>,
+    pub academic_term_id: Option<i32>,
+    pub import_batch_id: Option<uuid::Uuid>,
 }
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CreateRecordSchema {
     pub last_updated_by: String,
     pub first_name: String,
@@ -29,13 +50,25 @@ pub struct CreateRecordSchema {
     pub course: String,
     pub year_level: String,
     pub payment_for: String,
+    // Defaults to the payment category's default amount when omitted or empty.
+    #[serde(default)]
     pub amount: String,
     pub received_by: String,
+    // Falls back to the default receipt series when omitted.
+    pub receipt_series_id: Option<i32>,
+    // Date of the payment when it is entered after the fact; defaults to now.
+    pub created_at: Option<DateTime<Utc>>,
+    // Matched by name when omitted; see `students::resolve`.
+    pub student_id: Option<i32>,
+    // Inferred from `created_at` when omitted; see `terms::infer`.
+    pub academic_term_id: Option<i32>,
+    // Set by imports; never taken from a request body.
+    #[serde(skip)]
+    pub import_batch_id: Option<uuid::Uuid>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateRecordSchema {
-    pub id: i32,
     pub last_updated_by: String,
     pub first_name: String,
     pub last_name: String,
@@ -47,7 +80,216 @@ pub struct UpdateRecordSchema {
     pub received_by: String,
 }
 
+// Body of `PATCH /api/records/:id`. The outer `Option` is `None` when a field is
+// absent from the payload and `Some(None)` when it was sent as an explicit `null`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PatchRecordSchema {
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub last_updated_by: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub first_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub last_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub mi: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub course: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub year_level: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub payment_for: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub received_by: Option<Option<String>>,
+    // Relinks the record to another student, or unlinks it with `null`. The name fields
+    // keep what was printed on the receipt.
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub student_id: Option<Option<i32>>,
+    // Moves the record to another term, or leaves it without one with `null`.
+    #[serde(default, deserialize_with = "deserialize_present", skip_serializing_if = "Option::is_none")]
+    pub academic_term_id: Option<Option<i32>>,
+}
+
+impl PatchRecordSchema {
+    pub fn fields(&self) -> [(&'static str, &Option<Option<String>>); 9] {
+        [
+            ("last_updated_by", &self.last_updated_by),
+            ("first_name", &self.first_name),
+            ("last_name", &self.last_name),
+            ("mi", &self.mi),
+            ("course", &self.course),
+            ("year_level", &self.year_level),
+            ("payment_for", &self.payment_for),
+            ("amount", &self.amount),
+            ("received_by", &self.received_by),
+        ]
+    }
+}
+
+// A full update is a patch that sets every field.
+impl From<&UpdateRecordSchema> for PatchRecordSchema {
+    fn from(body: &UpdateRecordSchema) -> Self {
+        PatchRecordSchema {
+            last_updated_by: Some(Some(body.last_updated_by.to_owned())),
+            first_name: Some(Some(body.first_name.to_owned())),
+            last_name: Some(Some(body.last_name.to_owned())),
+            mi: Some(Some(body.mi.to_owned())),
+            course: Some(Some(body.course.to_owned())),
+            year_level: Some(Some(body.year_level.to_owned())),
+            payment_for: Some(Some(body.payment_for.to_owned())),
+            amount: Some(Some(body.amount.to_owned())),
+            received_by: Some(Some(body.received_by.to_owned())),
+            student_id: None,
+            academic_term_id: None,
+        }
+    }
+}
+
+fn deserialize_present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// Body of the deprecated `PUT /api/records`, which carries the id in the payload.
+#[derive(Debug, Deserialize)]
+pub struct LegacyUpdateRecordSchema {
+    pub id: i32,
+    #[serde(flatten)]
+    pub record: UpdateRecordSchema,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteRecordQuery {
+    pub reason: Option<String>
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicateQuery {
+    // Records the payment even though it looks like one already on file.
+    pub confirm_duplicate: Option<bool>,
+}
+
+// How a batch treats failures: `atomic` keeps nothing unless every item goes through;
+// `best_effort` keeps the items that did.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    #[default]
+    Atomic,
+    BestEffort,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchCreateSchema {
+    #[serde(default)]
+    pub mode: BatchMode,
+    // Applies `confirm_duplicate` to every item.
+    #[serde(default)]
+    pub confirm_duplicates: bool,
+    pub records: Vec<CreateRecordSchema>,
+}
+
+// One item of a batch update. `version` plays the part of the If-Match header.
+#[derive(Debug, Deserialize)]
+pub struct BatchUpdateItem {
+    pub id: i32,
+    pub version: i32,
+    pub changes: PatchRecordSchema,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchUpdateSchema {
+    #[serde(default)]
+    pub mode: BatchMode,
+    // Applies `confirm_duplicate` to every item.
+    #[serde(default)]
+    pub confirm_duplicates: bool,
+    pub records: Vec<BatchUpdateItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchDeleteItem {
+    pub id: i32,
+    pub version: i32,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchDeleteSchema {
+    #[serde(default)]
+    pub mode: BatchMode,
+    pub records: Vec<BatchDeleteItem>,
+}
+
+// Body of the deprecated `DELETE /api/records`.
 #[derive(Debug, Deserialize)]
 pub struct DeleteRecordSchema {
-    pub id: i32
-}
\ No newline at end of file
+    pub id: i32,
+    pub reason: Option<String>
+}
+#[derive(Debug, Deserialize)]
+pub struct VoidRecordSchema {
+    pub reason: String,
+    // Email or username of the approving admin, who signs off with their own password.
+    pub supervisor: String,
+    pub supervisor_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefundRecordSchema {
+    pub amount: String,
+    pub reason: String,
+}
+
+// Query string filters shared by the record listing, export and collections report.
+#[derive(Debug, Default, Deserialize)]
+pub struct RecordFilter {
+    pub academic_term_id: Option<i32>,
+    pub school_year: Option<String>,
+    pub semester: Option<String>,
+    // Only records in the term flagged as current.
+    pub current_term: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportColumnsQuery {
+    // Comma-separated column keys, e.g. "id,last_name,amount". All columns when absent.
+    pub columns: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CsvExportQuery {
+    pub delimiter: Option<String>,
+    // Prefix a UTF-8 byte order mark so Excel detects the encoding.
+    pub bom: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SummaryExportQuery {
+    // "course" (the default) or "payment_for".
+    pub group_by: Option<String>,
+}
+
+impl RecordFilter {
+    // The filters that were given, as "name = value", for export metadata.
+    pub fn describe(&self) -> Vec<String> {
+        let mut described = Vec::new();
+        if let Some(id) = self.academic_term_id {
+            described.push(format!("academic_term_id = {}", id));
+        }
+        if let Some(school_year) = &self.school_year {
+            described.push(format!("school_year = {}", school_year));
+        }
+        if let Some(semester) = &self.semester {
+            described.push(format!("semester = {}", semester));
+        }
+        if self.current_term == Some(true) {
+            described.push("current_term = true".to_string());
+        }
+        described
+    }
+}
@@ -0,0 +1,313 @@
+use std::sync::Arc;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use axum::response::IntoResponse;
+use chrono::{NaiveDate, Utc};
+use serde_json::json;
+use sqlx::{Postgres, QueryBuilder};
+use crate::AppState;
+use crate::amount;
+use crate::installments::AGING_BUCKETS;
+use crate::model::User;
+use crate::exports;
+use crate::record_handlers::handle_xlsx_error;
+use crate::record_model::{Record, RecordFilter};
+use crate::report_model::{AgingBucket, CollectionsQuery, CollectionsRow, DuplicatePairRow, DuplicatesQuery, OverdueQuery, OverdueRow};
+use crate::response::{bad_request, db_error};
+use crate::terms;
+use crate::xlsx;
+
+// Voids and refunds are dated when they are made, so a refund in a later period
+// reduces that period's net rather than rewriting the one the payment fell in.
+pub async fn get_collections_report(
+    State(data): State<Arc<AppState>>,
+    Query(params): Query<CollectionsQuery>,
+    Query(filter): Query<RecordFilter>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut query = QueryBuilder::<Postgres>::new(
+        "SELECT payment_for,
+            COUNT(*) FILTER (WHERE entry_type = 'payment') AS payments,
+            COUNT(*) FILTER (WHERE entry_type <> 'payment') AS reversals,
+            COALESCE(SUM(record_amount(amount)) FILTER (WHERE entry_type = 'payment'), 0)::NUMERIC(14, 2)::TEXT AS gross,
+            COALESCE(SUM(record_amount(amount)) FILTER (WHERE entry_type = 'refund'), 0)::NUMERIC(14, 2)::TEXT AS refunds,
+            COALESCE(SUM(record_amount(amount)) FILTER (WHERE entry_type = 'void'), 0)::NUMERIC(14, 2)::TEXT AS voids,
+            COALESCE(SUM(record_amount(amount)), 0)::NUMERIC(14, 2)::TEXT AS net,
+            COUNT(*) FILTER (WHERE record_amount(amount) IS NULL) AS unparseable
+        FROM \"records\"
+        WHERE deleted_at IS NULL"
+    );
+    if let Some(from) = params.from {
+        query.push(" AND created_at >= ").push_bind(from);
+    }
+    if let Some(to) = params.to {
+        query.push(" AND created_at < ").push_bind(to);
+    }
+    terms::push_filter(&mut query, &filter)?;
+    // The ROLLUP row, with no payment_for, carries the totals across all categories.
+    query.push(" GROUP BY ROLLUP (payment_for) ORDER BY payment_for NULLS LAST");
+
+    let mut rows: Vec<CollectionsRow> = query
+        .build_query_as::<CollectionsRow>()
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let totals = rows.pop();
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "from": params.from,
+            "to": params.to,
+            "rows": rows,
+            "totals": totals
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Installments of active plans that were due before `as_of` and are not fully paid.
+// Allocations count every payment on file, including ones made after `as_of`.
+async fn overdue_rows(
+    db: &sqlx::PgPool,
+    as_of: NaiveDate,
+) -> Result<Vec<OverdueRow>, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as::<_, OverdueRow>(
+        "WITH installments AS (
+            SELECT i.id, i.plan_id, i.sequence, i.due_date, i.amount,
+                COALESCE(SUM(record_amount(a.amount)), 0) AS paid,
+                record_amount(i.amount) - COALESCE(SUM(record_amount(a.amount)), 0) AS outstanding
+            FROM \"plan_installments\" i
+            JOIN \"payment_plans\" p ON p.id = i.plan_id AND p.status = 'active'
+            LEFT JOIN \"installment_allocations\" a ON a.installment_id = i.id
+            WHERE i.due_date < $1::DATE
+            GROUP BY i.id
+        )
+        SELECT s.id AS student_id, s.student_number, s.last_name, s.first_name, s.course, s.year_level,
+            p.id AS plan_id, p.payment_for, i.id AS installment_id, i.sequence, i.due_date, i.amount,
+            i.paid::NUMERIC(14, 2)::TEXT AS paid,
+            i.outstanding::NUMERIC(14, 2)::TEXT AS outstanding,
+            ($1::DATE - i.due_date) AS days_overdue,
+            CASE
+                WHEN $1::DATE - i.due_date <= 30 THEN '1-30'
+                WHEN $1::DATE - i.due_date <= 60 THEN '31-60'
+                WHEN $1::DATE - i.due_date <= 90 THEN '61-90'
+                ELSE '90+'
+            END AS bucket
+        FROM installments i
+        JOIN \"payment_plans\" p ON p.id = i.plan_id
+        JOIN \"students\" s ON s.id = p.student_id
+        WHERE i.outstanding > 0
+        ORDER BY days_overdue DESC, s.last_name, s.first_name, p.id, i.sequence"
+    )
+        .bind(as_of)
+        .fetch_all(db)
+        .await
+        .map_err(db_error)
+}
+
+fn aging_buckets(rows: &[OverdueRow]) -> Vec<AgingBucket> {
+    AGING_BUCKETS
+        .iter()
+        .map(|bucket| {
+            let in_bucket: Vec<&OverdueRow> = rows.iter().filter(|row| row.bucket == *bucket).collect();
+            AgingBucket {
+                bucket,
+                installments: in_bucket.len(),
+                outstanding: amount::format_cents(
+                    in_bucket.iter().filter_map(|row| amount::parse_cents(&row.outstanding)).sum(),
+                ),
+            }
+        })
+        .collect()
+}
+
+pub async fn get_overdue_report(
+    State(data): State<Arc<AppState>>,
+    Query(params): Query<OverdueQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let as_of = params.as_of.unwrap_or_else(|| Utc::now().date_naive());
+    let rows = overdue_rows(&data.db, as_of).await?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "as_of": as_of,
+            "buckets": aging_buckets(&rows),
+            "rows": rows
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+const OVERDUE_COLUMNS: [xlsx::Column; 14] = [
+    ("Student ID", 10.0),
+    ("Student Number", 15.0),
+    ("Last Name", 18.0),
+    ("First Name", 18.0),
+    ("Course", 10.0),
+    ("Year Level", 10.0),
+    ("Payment For", 18.0),
+    ("Installment", 11.0),
+    ("Due Date", 12.0),
+    ("Amount", 14.0),
+    ("Paid", 14.0),
+    ("Outstanding", 14.0),
+    ("Days Overdue", 12.0),
+    ("Bucket", 8.0),
+];
+
+fn write_overdue_workbook(
+    path: &str,
+    rows: &[OverdueRow],
+    properties: &xlsx::Properties,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let wb = handle_xlsx_error(xlsxwriter::Workbook::new(path))?;
+    let formats = xlsx::Formats::new();
+    let mut sheet = handle_xlsx_error(wb.add_worksheet(Some("Overdue")))?;
+    xlsx::write_header(&mut sheet, &formats, &OVERDUE_COLUMNS)?;
+
+    let mut totals = [0i64; 3];
+    for (i, row) in rows.iter().enumerate() {
+        let line = (i + 1) as u32;
+        handle_xlsx_error(sheet.write_number(line, 0, row.student_id.into(), None))?;
+        handle_xlsx_error(sheet.write_string(line, 1, row.student_number.as_deref().unwrap_or(""), None))?;
+        handle_xlsx_error(sheet.write_string(line, 2, &row.last_name, None))?;
+        handle_xlsx_error(sheet.write_string(line, 3, &row.first_name, None))?;
+        handle_xlsx_error(sheet.write_string(line, 4, &row.course, None))?;
+        handle_xlsx_error(sheet.write_string(line, 5, &row.year_level, None))?;
+        handle_xlsx_error(sheet.write_string(line, 6, &row.payment_for, None))?;
+        handle_xlsx_error(sheet.write_number(line, 7, row.sequence.into(), None))?;
+        xlsx::write_date(&mut sheet, line, 8, row.due_date, &formats.date)?;
+        for (offset, value) in [&row.amount, &row.paid, &row.outstanding].into_iter().enumerate() {
+            xlsx::write_money(&mut sheet, line, 9 + offset as u16, value, &formats.money)?;
+            totals[offset] += amount::parse_cents(value).unwrap_or(0);
+        }
+        handle_xlsx_error(sheet.write_number(line, 12, row.days_overdue.into(), None))?;
+        handle_xlsx_error(sheet.write_string(line, 13, &row.bucket, None))?;
+    }
+    xlsx::autofilter(&mut sheet, &OVERDUE_COLUMNS, rows.len())?;
+
+    let total_row = (rows.len() + 1) as u32;
+    handle_xlsx_error(sheet.write_string(total_row, 0, "Total", Some(&formats.total_label)))?;
+    for (offset, cents) in totals.iter().enumerate() {
+        xlsx::write_sum(&mut sheet, &formats, total_row, 9 + offset as u16, rows.len(), *cents)?;
+    }
+
+    let summary = total_row + 2;
+    handle_xlsx_error(sheet.write_string(summary, 0, "Bucket", Some(&formats.header)))?;
+    handle_xlsx_error(sheet.write_string(summary, 1, "Installments", Some(&formats.header)))?;
+    handle_xlsx_error(sheet.write_string(summary, 2, "Outstanding", Some(&formats.header)))?;
+    for (i, bucket) in aging_buckets(rows).iter().enumerate() {
+        let line = summary + 1 + i as u32;
+        handle_xlsx_error(sheet.write_string(line, 0, bucket.bucket, None))?;
+        handle_xlsx_error(sheet.write_number(line, 1, bucket.installments as f64, None))?;
+        xlsx::write_money(&mut sheet, line, 2, &bucket.outstanding, &formats.money)?;
+    }
+
+    xlsx::write_properties(&wb, &formats, properties)?;
+
+    handle_xlsx_error(wb.close())
+}
+
+// Same rows as `get_overdue_report`, followed by the bucket totals.
+pub async fn get_overdue_report_xlsx(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Query(params): Query<OverdueQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let as_of = params.as_of.unwrap_or_else(|| Utc::now().date_naive());
+    let rows = overdue_rows(&data.db, as_of).await?;
+
+    let properties = xlsx::Properties {
+        title: "Overdue installments",
+        author: &user.name,
+        generated_by: &user.username,
+        filters: vec![format!("as_of = {}", as_of)],
+    };
+    let path = exports::temp_path("xlsx");
+    if let Err(e) = write_overdue_workbook(&path.to_string_lossy(), &rows, &properties) {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(e);
+    }
+
+    exports::stream_temp(&path, &format!("overdue-aging-{}.xlsx", as_of)).await
+}
+
+// Pairs of live payments that the duplicate check would have flagged against each other:
+// same category and amount, same student or name, within the window. `from` and `to`
+// bound the earlier payment of each pair. A group of three shows up as three pairs.
+pub async fn get_duplicates_report(
+    State(data): State<Arc<AppState>>,
+    Query(params): Query<DuplicatesQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let window_hours = match params.window_hours {
+        Some(hours) if hours <= 0 => return Err(bad_request("window_hours must be positive")),
+        Some(hours) => hours,
+        None if data.env.duplicate_window_hours > 0 => data.env.duplicate_window_hours,
+        None => 24,
+    };
+
+    let pairs = sqlx::query_as::<_, DuplicatePairRow>(
+        "WITH payments AS (
+            SELECT r.* FROM \"records\" r
+            WHERE r.deleted_at IS NULL AND r.entry_type = 'payment'
+                AND NOT EXISTS (SELECT 1 FROM \"records\" v WHERE v.reverses_record_id = r.id AND v.entry_type = 'void')
+        )
+        SELECT a.id AS first_id, b.id AS second_id,
+            (ABS(EXTRACT(EPOCH FROM b.created_at - a.created_at)) / 60)::BIGINT AS minutes_apart
+        FROM payments a
+        JOIN payments b ON b.payment_for = a.payment_for
+            AND (b.created_at, b.id) > (a.created_at, a.id)
+            AND b.created_at <= a.created_at + make_interval(hours => $1)
+            AND record_amount(b.amount) = record_amount(a.amount)
+            AND (b.student_id = a.student_id
+                OR (student_name_key(b.first_name) = student_name_key(a.first_name) AND student_name_key(b.last_name) = student_name_key(a.last_name)))
+        WHERE ($2::TIMESTAMPTZ IS NULL OR a.created_at >= $2)
+            AND ($3::TIMESTAMPTZ IS NULL OR a.created_at < $3)
+        ORDER BY a.created_at, a.id, b.id"
+    )
+        .bind(window_hours as i32)
+        .bind(params.from)
+        .bind(params.to)
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let mut ids: Vec<i32> = pairs.iter().flat_map(|pair| [pair.first_id, pair.second_id]).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    let records = sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE id = ANY($1)",
+        &ids
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+    let record = |id: i32| records.iter().find(|record| record.id == id);
+
+    let rows: Vec<serde_json::Value> = pairs
+        .iter()
+        .map(|pair| json!({
+            "minutes_apart": pair.minutes_apart,
+            "records": [record(pair.first_id), record(pair.second_id)]
+        }))
+        .collect();
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "window_hours": window_hours,
+            "from": params.from,
+            "to": params.to,
+            "count": rows.len(),
+            "pairs": rows
+        })
+    });
+
+    Ok(Json(json_response))
+}
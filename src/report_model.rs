@@ -0,0 +1,72 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize)]
+pub struct CollectionsQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+// Sums are formatted by Postgres so they keep their exact decimal value.
+#[derive(Debug, Serialize, FromRow)]
+pub struct CollectionsRow {
+    pub payment_for: Option<String>,
+    pub payments: i64,
+    pub reversals: i64,
+    pub gross: String,
+    pub refunds: String,
+    pub voids: String,
+    pub net: String,
+    // Rows whose amount is not a plain number and is left out of the sums.
+    pub unparseable: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OverdueQuery {
+    // Age installments as of this day; defaults to today.
+    pub as_of: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgingBucket {
+    pub bucket: &'static str,
+    pub installments: usize,
+    pub outstanding: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OverdueRow {
+    pub student_id: i32,
+    pub student_number: Option<String>,
+    pub last_name: String,
+    pub first_name: String,
+    pub course: String,
+    pub year_level: String,
+    pub plan_id: i32,
+    pub payment_for: String,
+    pub installment_id: i32,
+    pub sequence: i32,
+    pub due_date: NaiveDate,
+    pub amount: String,
+    pub paid: String,
+    pub outstanding: String,
+    pub days_overdue: i32,
+    // "1-30", "31-60", "61-90" or "90+".
+    pub bucket: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicatesQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    // Defaults to DUPLICATE_WINDOW_HOURS, or 24 when the check is turned off.
+    pub window_hours: Option<i64>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct DuplicatePairRow {
+    pub first_id: i32,
+    pub second_id: i32,
+    pub minutes_apart: i64,
+}
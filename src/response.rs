@@ -1,5 +1,8 @@
+use axum::http::StatusCode;
+use axum::Json;
 use chrono::prelude::*;
 use serde::Serialize;
+use serde_json::json;
 
 #[allow(non_snake_case)]
 #[derive(Debug, Serialize)]
@@ -10,17 +13,56 @@ pub struct FilteredUser {
     pub username: String,
     pub role: String,
     pub photo: Option<String>,
+    pub office: Option<String>,
     pub createdAt: DateTime<Utc>,
     pub updatedAt: DateTime<Utc>
 }
 
-#[derive(Debug, Serialize)]
-pub struct UserData {
-    pub user: FilteredUser
+pub fn db_error(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = json!({
+        "status": "fail",
+        "message": format!("Database error: {}", e),
+    });
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
 }
 
-#[derive(Debug, Serialize)]
-pub struct UserResponse {
-    pub status: String,
-    pub data: UserData
-}
\ No newline at end of file
+pub fn not_found(message: &str) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = json!({
+        "status": "fail",
+        "message": message,
+    });
+    (StatusCode::NOT_FOUND, Json(error_response))
+}
+
+pub fn bad_request(message: &str) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = json!({
+        "status": "fail",
+        "message": message,
+    });
+    (StatusCode::BAD_REQUEST, Json(error_response))
+}
+
+pub fn forbidden(message: &str) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = json!({
+        "status": "fail",
+        "message": message,
+    });
+    (StatusCode::FORBIDDEN, Json(error_response))
+}
+
+pub fn conflict(message: &str) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = json!({
+        "status": "fail",
+        "message": message,
+    });
+    (StatusCode::CONFLICT, Json(error_response))
+}
+
+// Trims a mandatory text field, refusing it when nothing is left.
+pub fn required(field: &str, value: &str) -> Result<String, (StatusCode, Json<serde_json::Value>)> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(bad_request(&format!("Field '{}' cannot be empty", field)));
+    }
+    Ok(value.to_string())
+}
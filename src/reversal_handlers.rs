@@ -0,0 +1,298 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::{Extension, Json};
+use axum::response::IntoResponse;
+use serde_json::json;
+use sqlx::PgConnection;
+use crate::AppState;
+use crate::amount;
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::etag::{etag, if_match};
+use crate::handlers::verify_password;
+use crate::installments;
+use crate::ledger;
+use crate::model::User;
+use crate::record_handlers::{check_version, lock_record};
+use crate::record_model::{Record, RefundRecordSchema, VoidRecordSchema};
+use crate::response::{bad_request, conflict, db_error};
+use crate::terms;
+
+fn required_reason(reason: &str) -> Result<&str, (StatusCode, Json<serde_json::Value>)> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(bad_request("A reason is required"));
+    }
+    Ok(reason)
+}
+
+// The approving admin re-enters their own credentials; a cashier cannot approve
+// their own void even if they are an admin.
+async fn verify_supervisor(
+    conn: &mut PgConnection,
+    user: &User,
+    supervisor: &str,
+    password: &str,
+) -> Result<User, (StatusCode, Json<serde_json::Value>)> {
+    let approver = sqlx::query_as!(
+        User,
+        "SELECT * FROM \"users\" WHERE email = $1 OR username = $1",
+        supervisor.to_ascii_lowercase()
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .filter(|approver| approver.role == "admin" && verify_password(&approver.password, password));
+
+    let approver = approver.ok_or_else(|| {
+        let error_response = json!({
+            "status": "fail",
+            "message": "Supervisor approval failed: invalid supervisor credentials",
+        });
+        (StatusCode::FORBIDDEN, Json(error_response))
+    })?;
+
+    if approver.id == user.id {
+        let error_response = json!({
+            "status": "fail",
+            "message": "A void must be approved by a supervisor other than yourself",
+        });
+        return Err((StatusCode::FORBIDDEN, Json(error_response)));
+    }
+
+    Ok(approver)
+}
+
+// What is still left of a payment after earlier reversals, in cents.
+async fn remaining_cents(
+    conn: &mut PgConnection,
+    original: &Record,
+) -> Result<i64, (StatusCode, Json<serde_json::Value>)> {
+    if original.entry_type != "payment" {
+        return Err(bad_request("Only payments can be voided or refunded"));
+    }
+    let paid = amount::parse_cents(&original.amount).ok_or_else(|| {
+        let error_response = json!({
+            "status": "fail",
+            "message": format!("Amount {} is not a valid number and cannot be reversed", original.amount),
+        });
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(error_response))
+    })?;
+
+    let reversals = sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE reverses_record_id = $1 AND deleted_at IS NULL",
+        original.id
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    if reversals.iter().any(|reversal| reversal.entry_type == "void") {
+        return Err(conflict("Record has already been voided"));
+    }
+
+    let reversed: i64 = reversals
+        .iter()
+        .filter_map(|reversal| amount::parse_cents(&reversal.amount))
+        .sum();
+    Ok(paid + reversed)
+}
+
+struct Reversal<'a> {
+    entry_type: &'a str,
+    cents: i64,
+    reason: &'a str,
+    approved_by: Option<&'a str>,
+}
+
+// Inserts the reversing entry and finalizes the original. Both go through the audit
+// log and the ledger in the caller's transaction. The entry falls in the term it is
+// made in, like its date, rather than the term of the payment it reverses.
+async fn reverse(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    user: &User,
+    original: &Record,
+    reversal: Reversal<'_>,
+) -> Result<(Record, Record), (StatusCode, Json<serde_json::Value>)> {
+    let academic_term_id = terms::infer(&mut *conn, None).await?;
+    let entry = sqlx::query_as!(
+        Record,
+        "INSERT INTO \"records\" (last_updated_by,first_name,last_name,mi,course,year_level,payment_for,amount,received_by,entry_type,reverses_record_id,reversal_reason,approved_by,finalized_at,finalized_by,student_id,academic_term_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $1, $9, $10, $11, $12, NOW(), $1, $13, $14) RETURNING *",
+        user.username,
+        original.first_name,
+        original.last_name,
+        original.mi,
+        original.course,
+        original.year_level,
+        original.payment_for,
+        amount::format_cents(-reversal.cents),
+        reversal.entry_type,
+        original.id,
+        reversal.reason,
+        reversal.approved_by,
+        original.student_id,
+        academic_term_id
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut *conn, ctx, reversal.entry_type, "record", &entry.id.to_string(), None, Some(snapshot(&entry)))
+        .await
+        .map_err(db_error)?;
+    ledger::append(&mut *conn, reversal.entry_type, entry.id, Some(&entry))
+        .await
+        .map_err(db_error)?;
+    installments::rebuild_for(&mut *conn, &entry).await?;
+
+    let record = match original.finalized_at {
+        Some(_) => original.clone(),
+        None => finalize(conn, ctx, user, original).await?,
+    };
+
+    Ok((record, entry))
+}
+
+async fn finalize(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    user: &User,
+    before: &Record,
+) -> Result<Record, (StatusCode, Json<serde_json::Value>)> {
+    let record = sqlx::query_as!(
+        Record,
+        "UPDATE \"records\" SET finalized_at = NOW(), finalized_by = $1, updated_at = NOW(), version = version + 1 WHERE id = $2 RETURNING *",
+        user.username,
+        before.id
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut *conn, ctx, "finalize", "record", &record.id.to_string(), Some(snapshot(before)), Some(snapshot(&record)))
+        .await
+        .map_err(db_error)?;
+    ledger::append(&mut *conn, "finalize", record.id, Some(&record))
+        .await
+        .map_err(db_error)?;
+
+    Ok(record)
+}
+
+pub async fn finalize_record_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    ctx: AuditContext,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let expected_version = if_match(&headers)?.optional();
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_record(&mut tx, id).await?;
+    check_version(&before, expected_version)?;
+    if before.finalized_at.is_some() {
+        return Err(conflict("Record is already finalized"));
+    }
+
+    let record = finalize(&mut tx, &ctx, &user, &before).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let record_response = json!({"status": "success", "data": json!({
+        "record": record
+    })});
+
+    Ok(([(header::ETAG, etag(record.version))], Json(record_response)))
+}
+
+// Reverses whatever is left of the payment after earlier refunds.
+pub async fn void_record_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    ctx: AuditContext,
+    Json(body): Json<VoidRecordSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let expected_version = if_match(&headers)?.optional();
+    let reason = required_reason(&body.reason)?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let approver = verify_supervisor(&mut tx, &user, &body.supervisor, &body.supervisor_password).await?;
+
+    let original = lock_record(&mut tx, id).await?;
+    check_version(&original, expected_version)?;
+
+    let remaining = remaining_cents(&mut tx, &original).await?;
+    if remaining <= 0 {
+        return Err(conflict("Record has been fully refunded; there is nothing left to void"));
+    }
+
+    let (record, reversal) = reverse(&mut tx, &ctx, &user, &original, Reversal {
+        entry_type: "void",
+        cents: remaining,
+        reason,
+        approved_by: Some(&approver.username),
+    })
+        .await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let record_response = json!({"status": "success", "data": json!({
+        "record": record,
+        "reversal": reversal
+    })});
+
+    Ok((StatusCode::CREATED, [(header::ETAG, etag(record.version))], Json(record_response)))
+}
+
+pub async fn refund_record_handler(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    ctx: AuditContext,
+    Json(body): Json<RefundRecordSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let expected_version = if_match(&headers)?.optional();
+    let reason = required_reason(&body.reason)?;
+    let cents = amount::parse_cents(&body.amount)
+        .filter(|cents| *cents > 0)
+        .ok_or_else(|| bad_request("Refund amount must be a positive number"))?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let original = lock_record(&mut tx, id).await?;
+    check_version(&original, expected_version)?;
+
+    let remaining = remaining_cents(&mut tx, &original).await?;
+    if cents > remaining {
+        return Err(bad_request(&format!(
+            "Refund of {} exceeds the refundable balance of {}",
+            amount::format_cents(cents),
+            amount::format_cents(remaining.max(0))
+        )));
+    }
+
+    let (record, reversal) = reverse(&mut tx, &ctx, &user, &original, Reversal {
+        entry_type: "refund",
+        cents,
+        reason,
+        approved_by: None,
+    })
+        .await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let record_response = json!({"status": "success", "data": json!({
+        "record": record,
+        "reversal": reversal
+    })});
+
+    Ok((StatusCode::CREATED, [(header::ETAG, etag(record.version))], Json(record_response)))
+}
@@ -1,23 +1,36 @@
 use std::sync::Arc;
+use axum::extract::DefaultBodyLimit;
+use axum::http::HeaderValue;
 use axum::middleware;
-use axum::routing::{get, post, Router, put, delete};
+use axum::response::Response;
+use axum::routing::{get, post, Router, put, delete, patch};
+use crate::approval_handlers;
+use crate::audit_handlers;
+use crate::batch_handlers;
+use crate::catalog_handlers;
+use crate::export_handlers;
+use crate::fee_handlers;
+use crate::import_handlers;
 use crate::handlers;
 use crate::AppState;
 use crate::handlers::{get_me_handler, logout_handler};
+use crate::imports;
 use crate::jwt_auth::{admin_auth, auth};
+use crate::plan_handlers;
+use crate::receipt_handlers;
 use crate::record_handlers;
+use crate::report_handlers;
+use crate::reversal_handlers;
+use crate::student_handlers;
+use crate::term_handlers;
 
 pub fn create_router(app_state: Arc<AppState>) -> Router {
-    Router::new()
+    let router = Router::new()
         .route("/api/health", get(handlers::health_checker_handler))
         .route("/api/auth/register", post(handlers::register_user_handler))
         .route("/api/auth/login", post(handlers::login_user_handler))
         .route("/api/users/all", get(handlers::get_all_users_handler))
-        .route(
-            "/api/users/change_role",
-            put(handlers::change_role_handler)
-                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
-        )
+        .route("/api/verify/:token", get(receipt_handlers::verify_receipt_handler))
         .route(
             "/api/auth/logout",
             get(logout_handler)
@@ -28,6 +41,18 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
             get(get_me_handler)
                 .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
         )
+        .route(
+            "/api/users/:id",
+            get(handlers::get_user_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/users/:id",
+            put(handlers::replace_user_handler)
+                .patch(handlers::update_user_handler)
+                .delete(handlers::delete_user_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
         .route(
             "/api/records",
              post(record_handlers::create_record_handler)
@@ -39,15 +64,320 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
                 .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
         )
         .route(
-            "/api/records",
-            put(record_handlers::update_record_handler)
+            "/api/records/:id",
+            get(record_handlers::get_record_handler)
+                .put(record_handlers::update_record_handler)
+                .patch(record_handlers::patch_record_handler)
+                .delete(record_handlers::delete_record_handler)
                 .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
         )
         .route(
-            "/api/records",
-            delete(record_handlers::delete_record_handler)
+            "/api/records/batch",
+            post(batch_handlers::batch_create_records)
+                .patch(batch_handlers::batch_update_records)
+                .delete(batch_handlers::batch_delete_records)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/records/trash",
+            get(record_handlers::get_trashed_records)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/records/:id/restore",
+            post(record_handlers::restore_record_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/records/:id/purge",
+            delete(record_handlers::purge_record_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/records/:id/receipt.pdf",
+            get(receipt_handlers::get_record_receipt_pdf)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/records/:id/void",
+            post(reversal_handlers::void_record_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/records/:id/refund",
+            post(reversal_handlers::refund_record_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/records/:id/finalize",
+            post(reversal_handlers::finalize_record_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/reports/collections",
+            get(report_handlers::get_collections_report)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/reports/duplicates",
+            get(report_handlers::get_duplicates_report)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/reports/overdue",
+            get(report_handlers::get_overdue_report)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/reports/overdue.xlsx",
+            get(report_handlers::get_overdue_report_xlsx)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/records/:id/history",
+            get(audit_handlers::get_record_history)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/audit",
+            get(audit_handlers::get_audit_events)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/ledger/verify",
+            get(audit_handlers::verify_ledger_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/receipt-series",
+            get(receipt_handlers::get_receipt_series)
+                .post(receipt_handlers::create_receipt_series)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/receipt-series/report",
+            get(receipt_handlers::get_receipt_report)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/receipt-series/:id",
+            patch(receipt_handlers::update_receipt_series)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/catalogs",
+            get(catalog_handlers::get_catalogs)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/catalogs/courses",
+            post(catalog_handlers::create_course)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/catalogs/courses/:id",
+            patch(catalog_handlers::update_course)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/catalogs/year-levels",
+            post(catalog_handlers::create_year_level)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/catalogs/year-levels/:id",
+            patch(catalog_handlers::update_year_level)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/catalogs/payment-categories",
+            post(catalog_handlers::create_payment_category)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/catalogs/payment-categories/:id",
+            patch(catalog_handlers::update_payment_category)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/students",
+            get(student_handlers::get_students)
+                .post(student_handlers::create_student)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/students/review",
+            get(student_handlers::get_student_review)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/students/:id",
+            get(student_handlers::get_student)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/students/:id",
+            patch(student_handlers::update_student)
+                .delete(student_handlers::delete_student)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/students/:id/records",
+            get(student_handlers::get_student_records)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/students/:id/balance",
+            get(fee_handlers::get_student_balance)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/students/:id/payment-plans",
+            get(plan_handlers::get_student_payment_plans)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/students/:id/payment-plans",
+            post(plan_handlers::create_payment_plan)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/payment-plans/:id",
+            get(plan_handlers::get_payment_plan)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/payment-plans/:id/cancel",
+            post(plan_handlers::cancel_payment_plan)
                 .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
         )
+        .route(
+            "/api/academic-terms",
+            get(term_handlers::get_academic_terms)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/academic-terms",
+            post(term_handlers::create_academic_term)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/academic-terms/current",
+            get(term_handlers::get_current_academic_term)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/academic-terms/unlinked",
+            get(term_handlers::get_unlinked_terms)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/academic-terms/:id/link",
+            post(term_handlers::link_legacy_term)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/academic-terms/:id",
+            patch(term_handlers::update_academic_term)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/fee-schedules",
+            get(fee_handlers::get_fee_schedules)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/fee-schedules",
+            post(fee_handlers::create_fee_schedule)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/fee-schedules/:id",
+            patch(fee_handlers::update_fee_schedule)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/fee-schedules/:id/assess",
+            post(fee_handlers::assess_fee_schedule)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/students/:id/merge",
+            post(student_handlers::merge_students)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/approval-rules",
+            get(approval_handlers::get_approval_rules)
+                .post(approval_handlers::create_approval_rule)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/approval-rules/:id",
+            patch(approval_handlers::update_approval_rule)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/change-requests",
+            get(approval_handlers::get_change_requests)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/change-requests/:id",
+            get(approval_handlers::get_change_request)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/change-requests/:id/approve",
+            post(approval_handlers::approve_change_request)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/change-requests/:id/reject",
+            post(approval_handlers::reject_change_request)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/exports",
+            get(export_handlers::get_exports)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/exports/:id",
+            get(export_handlers::get_export)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/exports/:id/download",
+            get(export_handlers::download_export)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/records/export.xlsx",
+            get(record_handlers::export_records_xlsx_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/records/import",
+            post(import_handlers::import_records)
+                .layer(DefaultBodyLimit::max(imports::MAX_UPLOAD_BYTES))
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/records/export.csv",
+            get(record_handlers::export_records_csv_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/records/export.ndjson",
+            get(record_handlers::export_records_ndjson_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
+        .route(
+            "/api/records/export/summary.xlsx",
+            get(record_handlers::export_records_summary_xlsx_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        )
         .route(
             "/api/records/excel",
             get(record_handlers::create_excel_all_record)
@@ -57,6 +387,42 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
             "/api/records/download",
             get(record_handlers::download_excel_handler)
                 .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        );
+
+    let router = if app_state.env.legacy_routes {
+        router.merge(legacy_router(app_state.clone()))
+    } else {
+        router
+    };
+
+    router.with_state(app_state)
+}
+
+// Routes that take the target id in the JSON body. They predate the per-resource
+// routes above and are kept for older clients until `ENABLE_LEGACY_ROUTES` is turned off.
+fn legacy_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/api/users/change_role",
+            put(handlers::change_role_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .route(
+            "/api/records",
+            put(record_handlers::legacy_update_record_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
         )
-        .with_state(app_state)
+        .route(
+            "/api/records",
+            delete(record_handlers::legacy_delete_record_handler)
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth))
+        )
+        .layer(middleware::map_response(mark_deprecated))
+}
+
+async fn mark_deprecated(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert("deprecation", HeaderValue::from_static("true"));
+    response
 }
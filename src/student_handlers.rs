@@ -0,0 +1,461 @@
+use std::sync::Arc;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::IntoResponse;
+use serde_json::json;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use crate::AppState;
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::catalogs;
+use crate::installments;
+use crate::ledger;
+use crate::record_model::Record;
+use crate::response::{bad_request, conflict, db_error, not_found, required};
+use crate::student_model::{CourseConflict, CreateStudentSchema, MergeStudentSchema, NameVariantGroup, Student, StudentQuery, UpdateStudentSchema};
+use crate::students::validate_status;
+
+async fn ensure_unique_number(
+    conn: &mut PgConnection,
+    student_number: &str,
+    except_id: Option<i32>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let exists = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM \"students\" WHERE student_number = $1 AND id IS DISTINCT FROM $2)",
+        student_number,
+        except_id
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .unwrap_or(false);
+
+    if exists {
+        return Err(conflict("Student with that student number already exists"));
+    }
+    Ok(())
+}
+
+async fn lock_student(
+    conn: &mut PgConnection,
+    id: i32,
+) -> Result<Student, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query_as!(
+        Student,
+        "SELECT * FROM \"students\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No student found with the provided ID"))
+}
+
+pub async fn get_students(
+    State(data): State<Arc<AppState>>,
+    Query(params): Query<StudentQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM \"students\" WHERE TRUE");
+    if let Some(q) = params.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let pattern = format!("%{}%", q);
+        query
+            .push(" AND (student_number ILIKE ").push_bind(pattern.to_owned())
+            .push(" OR last_name ILIKE ").push_bind(pattern.to_owned())
+            .push(" OR first_name ILIKE ").push_bind(pattern.to_owned())
+            .push(" OR CONCAT_WS(' ', first_name, last_name) ILIKE ").push_bind(pattern)
+            .push(")");
+    }
+    if let Some(status) = params.status {
+        query.push(" AND status = ").push_bind(status);
+    }
+    if let Some(course) = params.course {
+        query.push(" AND course = ").push_bind(course);
+    }
+    query.push(" ORDER BY last_name, first_name, mi, id");
+
+    let students: Vec<Student> = query
+        .build_query_as::<Student>()
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "students": students
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn get_student(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let student = sqlx::query_as!(
+        Student,
+        "SELECT * FROM \"students\" WHERE id = $1",
+        id
+    )
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No student found with the provided ID"))?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "student": student
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn get_student_records(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let records = sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE student_id = $1 AND deleted_at IS NULL ORDER BY created_at, id",
+        id
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "records": records
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn create_student(
+    State(data): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Json(body): Json<CreateStudentSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let first_name = required("first_name", &body.first_name)?;
+    let last_name = required("last_name", &body.last_name)?;
+    let course = required("course", &body.course)?;
+    let year_level = required("year_level", &body.year_level)?;
+    let mi = body.mi.as_deref().map(str::trim).unwrap_or_default();
+    let student_number = body
+        .student_number
+        .as_deref()
+        .map(|student_number| required("student_number", student_number))
+        .transpose()?;
+    let status = body.status.unwrap_or_else(|| "active".to_string());
+    validate_status(&status)?;
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let course = catalogs::canonical(&mut tx, "course", &course).await?;
+    let year_level = catalogs::canonical(&mut tx, "year_level", &year_level).await?;
+    if let Some(student_number) = &student_number {
+        ensure_unique_number(&mut tx, student_number, None).await?;
+    }
+
+    let student = sqlx::query_as!(
+        Student,
+        "INSERT INTO \"students\" (student_number,first_name,last_name,mi,course,year_level,status) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+        student_number,
+        first_name,
+        last_name,
+        mi,
+        course,
+        year_level,
+        status
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "create", "student", &student.id.to_string(), None, Some(snapshot(&student)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "student": student
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+pub async fn update_student(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<UpdateStudentSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let first_name = body.first_name.as_deref().map(|value| required("first_name", value)).transpose()?;
+    let last_name = body.last_name.as_deref().map(|value| required("last_name", value)).transpose()?;
+    let course = body.course.as_deref().map(|value| required("course", value)).transpose()?;
+    let year_level = body.year_level.as_deref().map(|value| required("year_level", value)).transpose()?;
+    let student_number = body.student_number.as_deref().map(|value| required("student_number", value)).transpose()?;
+    let mi = body.mi.as_deref().map(str::trim);
+    if let Some(status) = &body.status {
+        validate_status(status)?;
+    }
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_student(&mut tx, id).await?;
+    let course = match course {
+        Some(course) => Some(catalogs::canonical(&mut tx, "course", &course).await?),
+        None => None,
+    };
+    let year_level = match year_level {
+        Some(year_level) => Some(catalogs::canonical(&mut tx, "year_level", &year_level).await?),
+        None => None,
+    };
+    if let Some(student_number) = &student_number {
+        ensure_unique_number(&mut tx, student_number, Some(id)).await?;
+    }
+
+    let student = sqlx::query_as!(
+        Student,
+        "UPDATE \"students\" SET student_number = COALESCE($1, student_number), first_name = COALESCE($2, first_name), last_name = COALESCE($3, last_name), mi = COALESCE($4, mi), course = COALESCE($5, course), year_level = COALESCE($6, year_level), status = COALESCE($7, status), updated_at = NOW() WHERE id = $8 RETURNING *",
+        student_number,
+        first_name,
+        last_name,
+        mi,
+        course,
+        year_level,
+        body.status,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "update", "student", &id.to_string(), Some(snapshot(&before)), Some(snapshot(&student)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "student": student
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Students with payments on file, even deleted ones, cannot be removed; merge them
+// into the right student or mark them inactive instead.
+pub async fn delete_student(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = lock_student(&mut tx, id).await?;
+
+    let linked = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM \"records\" WHERE student_id = $1",
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .unwrap_or(0);
+    if linked > 0 {
+        return Err(conflict(&format!("Student has {} linked records; merge or deactivate them instead", linked)));
+    }
+
+    sqlx::query!("DELETE FROM \"students\" WHERE id = $1", id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "delete", "student", &id.to_string(), Some(snapshot(&before)), None)
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Moves every record, assessment and payment plan of a duplicate student onto this one
+// and deletes the duplicate.
+// Each moved record gets a new version and ledger entry like any other edit.
+pub async fn merge_students(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<MergeStudentSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if body.student_id == id {
+        return Err(bad_request("A student cannot be merged into itself"));
+    }
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let student = lock_student(&mut tx, id).await?;
+    let duplicate = lock_student(&mut tx, body.student_id).await?;
+
+    let before = sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE student_id = $1 ORDER BY id FOR UPDATE",
+        duplicate.id
+    )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    let moved = sqlx::query_as!(
+        Record,
+        "UPDATE \"records\" SET student_id = $1, updated_at = NOW(), version = version + 1 WHERE student_id = $2 RETURNING *",
+        student.id,
+        duplicate.id
+    )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    for record in &moved {
+        let previous = before.iter().find(|previous| previous.id == record.id);
+        log_event(&mut tx, &ctx, "update", "record", &record.id.to_string(), previous.map(snapshot), Some(snapshot(record)))
+            .await
+            .map_err(db_error)?;
+        ledger::append(&mut tx, "update", record.id, Some(record))
+            .await
+            .map_err(db_error)?;
+    }
+
+    // Both students billed for the same fee in the same term is a double assessment
+    // someone has to resolve by hand, not something to pick a winner for here.
+    let clashes = sqlx::query_scalar!(
+        "SELECT t.school_year || ' ' || t.semester || ' ' || d.payment_for FROM \"assessments\" d JOIN \"assessments\" s ON s.student_id = $1 AND s.academic_term_id = d.academic_term_id AND s.payment_for = d.payment_for JOIN \"academic_terms\" t ON t.id = d.academic_term_id WHERE d.student_id = $2 ORDER BY 1",
+        student.id,
+        duplicate.id
+    )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    if !clashes.is_empty() {
+        let clashes: Vec<String> = clashes.into_iter().flatten().collect();
+        return Err(conflict(&format!("Both students are assessed for: {}", clashes.join(", "))));
+    }
+
+    sqlx::query!(
+        "UPDATE \"assessments\" SET student_id = $1 WHERE student_id = $2",
+        student.id,
+        duplicate.id
+    )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    let plan_categories = sqlx::query_scalar!(
+        "UPDATE \"payment_plans\" SET student_id = $1, updated_at = NOW() WHERE student_id = $2 RETURNING payment_for",
+        student.id,
+        duplicate.id
+    )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    // The survivor's plans now see the moved payments, and the moved plans see theirs.
+    let mut categories: Vec<&str> = moved
+        .iter()
+        .map(|record| record.payment_for.as_str())
+        .chain(plan_categories.iter().map(String::as_str))
+        .collect();
+    categories.sort_unstable();
+    categories.dedup();
+    for payment_for in categories {
+        installments::rebuild(&mut tx, student.id, payment_for).await?;
+    }
+
+    sqlx::query!("DELETE FROM \"students\" WHERE id = $1", duplicate.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    log_event(&mut tx, &ctx, "merge", "student", &duplicate.id.to_string(), Some(snapshot(&duplicate)), Some(snapshot(&student)))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "student": student,
+            "moved_records": moved.len()
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Matches a person should look at: the migration and `students::resolve` only link
+// names that normalize to the same key, so near misses end up here.
+pub async fn get_student_review(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let name_variants: Vec<NameVariantGroup> = sqlx::query_as::<_, NameVariantGroup>(
+        "SELECT MIN(last_name) AS last_name, MIN(first_name) AS first_name,
+            ARRAY_AGG(id ORDER BY id) AS student_ids,
+            ARRAY_AGG(mi ORDER BY id) AS middle_initials
+        FROM \"students\"
+        GROUP BY student_name_key(last_name), student_name_key(first_name)
+        HAVING COUNT(*) > 1
+        ORDER BY 1, 2"
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let course_conflicts: Vec<CourseConflict> = sqlx::query_as::<_, CourseConflict>(
+        "SELECT s.id AS student_id, s.last_name, s.first_name, s.mi,
+            ARRAY_AGG(DISTINCT BTRIM(r.course) ORDER BY BTRIM(r.course)) AS courses,
+            COUNT(*) AS records
+        FROM \"students\" s
+        JOIN \"records\" r ON r.student_id = s.id
+        WHERE r.deleted_at IS NULL AND r.entry_type = 'payment'
+        GROUP BY s.id
+        HAVING COUNT(DISTINCT student_name_key(r.course)) > 1
+        ORDER BY s.last_name, s.first_name, s.id"
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let unlinked = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM \"records\" WHERE student_id IS NULL AND deleted_at IS NULL"
+    )
+        .fetch_one(&data.db)
+        .await
+        .map_err(db_error)?
+        .unwrap_or(0);
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "name_variants": name_variants,
+            "course_conflicts": course_conflicts,
+            "unlinked_records": unlinked
+        })
+    });
+
+    Ok(Json(json_response))
+}
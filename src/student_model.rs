@@ -0,0 +1,77 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct Student {
+    pub id: i32,
+    pub student_number: Option<String>,
+    pub first_name: String,
+    pub last_name: String,
+    pub mi: String,
+    pub course: String,
+    pub year_level: String,
+    pub status: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateStudentSchema {
+    pub student_number: Option<String>,
+    pub first_name: String,
+    pub last_name: String,
+    pub mi: Option<String>,
+    pub course: String,
+    pub year_level: String,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateStudentSchema {
+    pub student_number: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub mi: Option<String>,
+    pub course: Option<String>,
+    pub year_level: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StudentQuery {
+    // Matched against the student number and names.
+    pub q: Option<String>,
+    pub status: Option<String>,
+    pub course: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeStudentSchema {
+    // The duplicate whose records move over; it is deleted afterwards.
+    pub student_id: i32,
+}
+
+// Students that share a last and first name but were kept apart because their middle
+// initials differ, e.g. "D" on some receipts and nothing on others.
+#[derive(Debug, FromRow, Serialize)]
+pub struct NameVariantGroup {
+    pub last_name: String,
+    pub first_name: String,
+    pub student_ids: Vec<i32>,
+    pub middle_initials: Vec<String>,
+}
+
+// Students whose linked payments were recorded under more than one course, which
+// usually means two people with the same name were merged into one.
+#[derive(Debug, FromRow, Serialize)]
+pub struct CourseConflict {
+    pub student_id: i32,
+    pub last_name: String,
+    pub first_name: String,
+    pub mi: String,
+    pub courses: Vec<String>,
+    pub records: i64,
+}
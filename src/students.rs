@@ -0,0 +1,60 @@
+use axum::http::StatusCode;
+use axum::Json;
+use serde_json::Value;
+use sqlx::PgConnection;
+use crate::response::{bad_request, db_error};
+use crate::student_model::Student;
+
+pub const STUDENT_STATUSES: [&str; 4] = ["active", "inactive", "graduated", "withdrawn"];
+
+pub fn validate_status(status: &str) -> Result<(), (StatusCode, Json<Value>)> {
+    if !STUDENT_STATUSES.contains(&status) {
+        return Err(bad_request(&format!("Invalid status: {}. Expected one of: {}", status, STUDENT_STATUSES.join(", "))));
+    }
+    Ok(())
+}
+
+pub async fn check_student(
+    conn: &mut PgConnection,
+    id: i32,
+) -> Result<Student, (StatusCode, Json<Value>)> {
+    sqlx::query_as!(
+        Student,
+        "SELECT * FROM \"students\" WHERE id = $1",
+        id
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| bad_request(&format!("No student found with ID {}", id)))
+}
+
+// The student a new payment belongs to: the one named by id, or else the only student
+// whose normalized name matches. Unknown or ambiguous names are left unlinked for the
+// review report rather than guessed.
+pub async fn resolve(
+    conn: &mut PgConnection,
+    student_id: Option<i32>,
+    first_name: &str,
+    last_name: &str,
+    mi: &str,
+) -> Result<Option<i32>, (StatusCode, Json<Value>)> {
+    if let Some(id) = student_id {
+        return check_student(conn, id).await.map(|student| Some(student.id));
+    }
+
+    let matches = sqlx::query_scalar!(
+        "SELECT id FROM \"students\" WHERE student_name_key(last_name) = student_name_key($1) AND student_name_key(first_name) = student_name_key($2) AND student_name_key(mi) = student_name_key($3) LIMIT 2",
+        last_name,
+        first_name,
+        mi
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    Ok(match matches.as_slice() {
+        [id] => Some(*id),
+        _ => None,
+    })
+}
@@ -0,0 +1,343 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::IntoResponse;
+use chrono::NaiveDate;
+use serde_json::json;
+use sqlx::PgConnection;
+use crate::AppState;
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::response::{bad_request, conflict, db_error, not_found};
+use crate::term_model::{AcademicTerm, CreateAcademicTermSchema, LinkTermSchema, UnlinkedTerm, UpdateAcademicTermSchema};
+use crate::terms::{self, validate_school_year, validate_semester};
+
+// Terms may not overlap, or a payment date would fall in two of them. The
+// `academic_terms_no_overlap` constraint enforces the same.
+async fn check_dates(
+    conn: &mut PgConnection,
+    school_year: &str,
+    semester: &str,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    except_id: Option<i32>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if end_date < start_date {
+        return Err(bad_request("end_date cannot be before start_date"));
+    }
+
+    let duplicate = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM \"academic_terms\" WHERE school_year = $1 AND semester = $2 AND id IS DISTINCT FROM $3)",
+        school_year,
+        semester,
+        except_id
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .unwrap_or(false);
+    if duplicate {
+        return Err(conflict(&format!("The {} semester of {} already exists", semester, school_year)));
+    }
+
+    let overlapping = sqlx::query_as!(
+        AcademicTerm,
+        "SELECT * FROM \"academic_terms\" WHERE start_date <= $2 AND end_date >= $1 AND id IS DISTINCT FROM $3 ORDER BY start_date LIMIT 1",
+        start_date,
+        end_date,
+        except_id
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    if let Some(term) = overlapping {
+        return Err(conflict(&format!(
+            "Dates overlap the {} semester of {} ({} to {})",
+            term.semester, term.school_year, term.start_date, term.end_date
+        )));
+    }
+
+    Ok(())
+}
+
+// `check_dates` gives the friendly message; the exclusion constraint catches a
+// concurrent write that slipped past it.
+fn write_error(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    match &e {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23P01") => {
+            conflict("Dates overlap another academic term")
+        }
+        sqlx::Error::Database(db_err) if db_err.constraint() == Some("academic_terms_school_year_semester_key") => {
+            conflict("That semester of the school year already exists")
+        }
+        _ => db_error(e),
+    }
+}
+
+async fn clear_current(
+    conn: &mut PgConnection,
+    except_id: Option<i32>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query!(
+        "UPDATE \"academic_terms\" SET is_current = FALSE, updated_at = NOW() WHERE is_current AND id IS DISTINCT FROM $1",
+        except_id
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    Ok(())
+}
+
+pub async fn get_academic_terms(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let terms = sqlx::query_as!(
+        AcademicTerm,
+        "SELECT * FROM \"academic_terms\" ORDER BY start_date DESC"
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "academic_terms": terms
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn get_current_academic_term(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let term = sqlx::query_as!(
+        AcademicTerm,
+        "SELECT * FROM \"academic_terms\" WHERE is_current"
+    )
+        .fetch_optional(&data.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No academic term is marked as current"))?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "academic_term": term
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn create_academic_term(
+    State(data): State<Arc<AppState>>,
+    ctx: AuditContext,
+    Json(body): Json<CreateAcademicTermSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let school_year = validate_school_year(&body.school_year)?;
+    let semester = validate_semester(&body.semester)?;
+    let is_current = body.is_current.unwrap_or(false);
+
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    check_dates(&mut tx, &school_year, &semester, body.start_date, body.end_date, None).await?;
+    if is_current {
+        clear_current(&mut tx, None).await?;
+    }
+
+    let term = sqlx::query_as!(
+        AcademicTerm,
+        "INSERT INTO \"academic_terms\" (school_year,semester,start_date,end_date,is_current) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+        school_year,
+        semester,
+        body.start_date,
+        body.end_date,
+        is_current
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(write_error)?;
+
+    log_event(&mut tx, &ctx, "create", "academic_term", &term.id.to_string(), None, Some(snapshot(&term)))
+        .await
+        .map_err(db_error)?;
+
+    let assigned_records = terms::assign_records(&mut tx, &ctx, &term).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "academic_term": term,
+            "assigned_records": assigned_records
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+// Widening a term picks up records that had no term; records already placed keep
+// their term when the dates move away from them.
+pub async fn update_academic_term(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<UpdateAcademicTermSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let before = sqlx::query_as!(
+        AcademicTerm,
+        "SELECT * FROM \"academic_terms\" WHERE id = $1 FOR UPDATE",
+        id
+    )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| not_found("No academic term found with the provided ID"))?;
+
+    let school_year = match &body.school_year {
+        Some(school_year) => validate_school_year(school_year)?,
+        None => before.school_year.to_owned(),
+    };
+    let semester = match &body.semester {
+        Some(semester) => validate_semester(semester)?,
+        None => before.semester.to_owned(),
+    };
+    let start_date = body.start_date.unwrap_or(before.start_date);
+    let end_date = body.end_date.unwrap_or(before.end_date);
+    check_dates(&mut tx, &school_year, &semester, start_date, end_date, Some(id)).await?;
+
+    if body.is_current == Some(true) {
+        clear_current(&mut tx, Some(id)).await?;
+    }
+
+    let term = sqlx::query_as!(
+        AcademicTerm,
+        "UPDATE \"academic_terms\" SET school_year = $1, semester = $2, start_date = $3, end_date = $4, is_current = COALESCE($5, is_current), legacy_overlap = FALSE, updated_at = NOW() WHERE id = $6 RETURNING *",
+        school_year,
+        semester,
+        start_date,
+        end_date,
+        body.is_current,
+        id
+    )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(write_error)?;
+
+    log_event(&mut tx, &ctx, "update", "academic_term", &id.to_string(), Some(snapshot(&before)), Some(snapshot(&term)))
+        .await
+        .map_err(db_error)?;
+
+    let assigned_records = terms::assign_records(&mut tx, &ctx, &term).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "academic_term": term,
+            "assigned_records": assigned_records
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Free-text terms left over from before terms were a table, with how many fee
+// schedules and assessments still carry each one.
+pub async fn get_unlinked_terms(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let unlinked = sqlx::query_as!(
+        UnlinkedTerm,
+        "SELECT legacy_term, SUM(fee_schedules)::BIGINT AS fee_schedules, SUM(assessments)::BIGINT AS assessments
+        FROM (
+            SELECT legacy_term, 1 AS fee_schedules, 0 AS assessments FROM \"fee_schedules\" WHERE academic_term_id IS NULL
+            UNION ALL
+            SELECT legacy_term, 0, 1 FROM \"assessments\" WHERE academic_term_id IS NULL
+        ) AS legacy
+        GROUP BY legacy_term
+        ORDER BY legacy_term"
+    )
+        .fetch_all(&data.db)
+        .await
+        .map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "unlinked_terms": unlinked
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Links every unlinked fee schedule and assessment carrying `legacy_term` to this term.
+// A row that would duplicate one already in the term is refused rather than merged.
+pub async fn link_legacy_term(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    ctx: AuditContext,
+    Json(body): Json<LinkTermSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = data.db.begin().await.map_err(db_error)?;
+
+    let term = terms::check_term(&mut tx, id).await?;
+
+    let link_error = |e: sqlx::Error| match &e {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => conflict(&format!(
+            "The {} semester of {} already has a fee schedule or assessment that {:?} would duplicate",
+            term.semester, term.school_year, body.legacy_term
+        )),
+        _ => db_error(e),
+    };
+
+    let fee_schedules = sqlx::query_scalar!(
+        "UPDATE \"fee_schedules\" SET academic_term_id = $1, legacy_term = NULL, updated_at = NOW() WHERE academic_term_id IS NULL AND legacy_term = $2 RETURNING id",
+        term.id,
+        body.legacy_term
+    )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(link_error)?;
+
+    let assessments = sqlx::query_scalar!(
+        "UPDATE \"assessments\" SET academic_term_id = $1, legacy_term = NULL WHERE academic_term_id IS NULL AND legacy_term = $2 RETURNING id",
+        term.id,
+        body.legacy_term
+    )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(link_error)?;
+
+    if fee_schedules.is_empty() && assessments.is_empty() {
+        return Err(not_found(&format!("No unlinked fee schedule or assessment has the term {:?}", body.legacy_term)));
+    }
+
+    log_event(&mut tx, &ctx, "link", "academic_term", &term.id.to_string(), None, Some(json!({
+        "legacy_term": body.legacy_term,
+        "fee_schedules": fee_schedules,
+        "assessments": assessments
+    })))
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let json_response = json!({
+        "status": "success",
+        "data": json!({
+            "academic_term": term,
+            "fee_schedules": fee_schedules,
+            "assessments": assessments
+        })
+    });
+
+    Ok(Json(json_response))
+}
@@ -0,0 +1,52 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize, FromRow, Serialize, Clone)]
+pub struct AcademicTerm {
+    pub id: i32,
+    pub school_year: String,
+    // "first", "second" or "summer".
+    pub semester: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub is_current: bool,
+    // Set on terms that already overlapped another when overlaps became a constraint.
+    // Cleared by the next successful update, which cannot leave an overlap.
+    pub legacy_overlap: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAcademicTermSchema {
+    pub school_year: String,
+    pub semester: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub is_current: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAcademicTermSchema {
+    pub school_year: Option<String>,
+    pub semester: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub is_current: Option<bool>,
+}
+
+// Free-text terms of fee schedules and assessments that are not linked to a term yet.
+#[derive(Debug, FromRow, Serialize)]
+pub struct UnlinkedTerm {
+    pub legacy_term: Option<String>,
+    pub fee_schedules: Option<i64>,
+    pub assessments: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkTermSchema {
+    pub legacy_term: String,
+}
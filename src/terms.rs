@@ -0,0 +1,148 @@
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use crate::audit::{log_event, snapshot, AuditContext};
+use crate::ledger;
+use crate::record_model::{Record, RecordFilter};
+use crate::response::{bad_request, db_error};
+use crate::term_model::AcademicTerm;
+
+pub const SEMESTERS: [&str; 3] = ["first", "second", "summer"];
+
+pub fn validate_semester(semester: &str) -> Result<String, (StatusCode, Json<Value>)> {
+    let semester = semester.trim().to_lowercase();
+    if !SEMESTERS.contains(&semester.as_str()) {
+        return Err(bad_request(&format!("Invalid semester: {}. Expected one of: {}", semester, SEMESTERS.join(", "))));
+    }
+    Ok(semester)
+}
+
+// School years span two consecutive calendar years, written "2026-2027".
+pub fn validate_school_year(school_year: &str) -> Result<String, (StatusCode, Json<Value>)> {
+    let school_year = school_year.trim();
+    let years: Option<(i32, i32)> = school_year
+        .split_once('-')
+        .filter(|(start, end)| start.len() == 4 && end.len() == 4)
+        .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)));
+    match years {
+        Some((start, end)) if end == start + 1 => Ok(school_year.to_string()),
+        _ => Err(bad_request("school_year must look like 2026-2027")),
+    }
+}
+
+pub async fn check_term(
+    conn: &mut PgConnection,
+    id: i32,
+) -> Result<AcademicTerm, (StatusCode, Json<Value>)> {
+    sqlx::query_as!(
+        AcademicTerm,
+        "SELECT * FROM \"academic_terms\" WHERE id = $1",
+        id
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| bad_request(&format!("No academic term found with ID {}", id)))
+}
+
+// The term whose dates cover `at` (now when `None`). Terms cannot overlap, so there is
+// at most one.
+pub async fn infer(
+    conn: &mut PgConnection,
+    at: Option<DateTime<Utc>>,
+) -> Result<Option<i32>, (StatusCode, Json<Value>)> {
+    sqlx::query_scalar!(
+        "SELECT id FROM \"academic_terms\" WHERE COALESCE($1, NOW())::DATE BETWEEN start_date AND end_date",
+        at
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)
+}
+
+// The term a new record goes in: the one named by id, or else the one covering its date.
+pub async fn resolve(
+    conn: &mut PgConnection,
+    academic_term_id: Option<i32>,
+    at: Option<DateTime<Utc>>,
+) -> Result<Option<i32>, (StatusCode, Json<Value>)> {
+    match academic_term_id {
+        Some(id) => check_term(conn, id).await.map(|term| Some(term.id)),
+        None => infer(conn, at).await,
+    }
+}
+
+// Puts records that have no term yet into `term` when their date falls inside it.
+// Records already in another term are left where they are, even if the dates changed.
+pub async fn assign_records(
+    conn: &mut PgConnection,
+    ctx: &AuditContext,
+    term: &AcademicTerm,
+) -> Result<usize, (StatusCode, Json<Value>)> {
+    let before = sqlx::query_as!(
+        Record,
+        "SELECT * FROM \"records\" WHERE academic_term_id IS NULL AND created_at::DATE BETWEEN $1 AND $2 ORDER BY id FOR UPDATE",
+        term.start_date,
+        term.end_date
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    if before.is_empty() {
+        return Ok(0);
+    }
+    let ids: Vec<i32> = before.iter().map(|record| record.id).collect();
+
+    let assigned = sqlx::query_as!(
+        Record,
+        "UPDATE \"records\" SET academic_term_id = $1, updated_at = NOW(), version = version + 1 WHERE id = ANY($2) RETURNING *",
+        term.id,
+        &ids
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    for record in &assigned {
+        let previous = before.iter().find(|previous| previous.id == record.id);
+        log_event(&mut *conn, ctx, "update", "record", &record.id.to_string(), previous.map(snapshot), Some(snapshot(record)))
+            .await
+            .map_err(db_error)?;
+        ledger::append(&mut *conn, "update", record.id, Some(record))
+            .await
+            .map_err(db_error)?;
+    }
+
+    Ok(assigned.len())
+}
+
+// Appends the term conditions of `filter` to a query that already has a WHERE clause.
+pub fn push_filter(
+    query: &mut QueryBuilder<Postgres>,
+    filter: &RecordFilter,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    if let Some(id) = filter.academic_term_id {
+        query.push(" AND academic_term_id = ").push_bind(id);
+    }
+
+    let current = filter.current_term.unwrap_or(false);
+    if filter.school_year.is_none() && filter.semester.is_none() && !current {
+        return Ok(());
+    }
+
+    query.push(" AND academic_term_id IN (SELECT id FROM \"academic_terms\" WHERE TRUE");
+    if let Some(school_year) = &filter.school_year {
+        query.push(" AND school_year = ").push_bind(validate_school_year(school_year)?);
+    }
+    if let Some(semester) = &filter.semester {
+        query.push(" AND semester = ").push_bind(validate_semester(semester)?);
+    }
+    if current {
+        query.push(" AND is_current");
+    }
+    query.push(")");
+
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::{DateTime, Datelike, Local, NaiveDate, Timelike, Utc};
+use serde_json::Value;
+use xlsxwriter::{Format, Workbook, Worksheet};
+use crate::amount;
+use crate::record_handlers::handle_xlsx_error;
+
+const MONEY_FORMAT: &str = "#,##0.00;[Red]-#,##0.00";
+const DATE_FORMAT: &str = "yyyy-mm-dd";
+const DATETIME_FORMAT: &str = "yyyy-mm-dd hh:mm";
+
+// Cell formats shared by every exported workbook.
+pub struct Formats {
+    pub header: Format,
+    pub money: Format,
+    pub date: Format,
+    pub datetime: Format,
+    pub total_label: Format,
+    pub total_money: Format,
+}
+
+impl Formats {
+    pub fn new() -> Self {
+        let mut header = Format::new();
+        header.set_bold();
+        let mut money = Format::new();
+        money.set_num_format(MONEY_FORMAT);
+        let mut date = Format::new();
+        date.set_num_format(DATE_FORMAT);
+        let mut datetime = Format::new();
+        datetime.set_num_format(DATETIME_FORMAT);
+        let mut total_label = Format::new();
+        total_label.set_bold();
+        let mut total_money = Format::new();
+        total_money.set_bold().set_num_format(MONEY_FORMAT);
+
+        Formats {
+            header,
+            money,
+            date,
+            datetime,
+            total_label,
+            total_money,
+        }
+    }
+}
+
+// A header cell and the width of its column, in characters.
+pub type Column = (&'static str, f64);
+
+// Bold header row, frozen so it stays visible while scrolling.
+pub fn write_header(
+    sheet: &mut Worksheet,
+    formats: &Formats,
+    columns: &[Column],
+) -> Result<(), (StatusCode, Json<Value>)> {
+    for (i, (header, width)) in columns.iter().enumerate() {
+        handle_xlsx_error(sheet.write_string(0, i as u16, header, Some(&formats.header)))?;
+        handle_xlsx_error(sheet.set_column(i as u16, i as u16, *width, None))?;
+    }
+    sheet.freeze_panes(1, 0);
+    Ok(())
+}
+
+// Filter dropdowns over the header and the `rows` data rows below it.
+pub fn autofilter(
+    sheet: &mut Worksheet,
+    columns: &[Column],
+    rows: usize,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    handle_xlsx_error(sheet.autofilter(0, 0, rows as u32, (columns.len() - 1) as u16))
+}
+
+// Amounts are stored as text; anything that does not parse is written as it is so the
+// cell still shows what was entered.
+pub fn write_money(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: &str,
+    format: &Format,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    match amount::parse_cents(value) {
+        Some(cents) => handle_xlsx_error(sheet.write_number(row, col, cents as f64 / 100.0, Some(format))),
+        None => handle_xlsx_error(sheet.write_string(row, col, value, None)),
+    }
+}
+
+// Timestamps are shown in the server's local timezone; Excel has no notion of offsets.
+pub fn write_datetime(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: Option<DateTime<Utc>>,
+    format: &Format,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let Some(value) = value else {
+        return handle_xlsx_error(sheet.write_blank(row, col, None));
+    };
+    let local = value.with_timezone(&Local);
+    let cell = xlsxwriter::worksheet::DateTime::new(
+        local.year() as i16,
+        local.month() as i8,
+        local.day() as i8,
+        local.hour() as i8,
+        local.minute() as i8,
+        local.second() as f64,
+    );
+    handle_xlsx_error(sheet.write_datetime(row, col, &cell, Some(format)))
+}
+
+pub fn write_date(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: NaiveDate,
+    format: &Format,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let cell = xlsxwriter::worksheet::DateTime::date(value.year() as i16, value.month() as i8, value.day() as i8);
+    handle_xlsx_error(sheet.write_datetime(row, col, &cell, Some(format)))
+}
+
+// "A" for 0, "AA" for 26, as used in cell references.
+pub fn column_name(col: u16) -> String {
+    let mut col = col as u32 + 1;
+    let mut name = String::new();
+    while col > 0 {
+        let rem = (col - 1) % 26;
+        name.insert(0, (b'A' + rem as u8) as char);
+        col = (col - 1) / 26;
+    }
+    name
+}
+
+// A cell reference such as "C4" for zero-based `row` and `col`.
+pub fn cell(row: u32, col: u16) -> String {
+    format!("{}{}", column_name(col), row + 1)
+}
+
+// Excel sheet names are at most 31 characters, cannot contain []:*?/\ and must be
+// unique regardless of case.
+pub fn sheet_name(value: &str, taken: &[String]) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| if "[]:*?/\\".contains(c) { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_matches('\'');
+    let base: String = if cleaned.is_empty() { "(blank)".to_string() } else { cleaned.chars().take(31).collect() };
+
+    let mut name = base.to_owned();
+    let mut n = 2;
+    while taken.iter().any(|taken| taken.eq_ignore_ascii_case(&name)) {
+        let suffix = format!(" ({})", n);
+        name = format!("{}{}", base.chars().take(31 - suffix.len()).collect::<String>(), suffix);
+        n += 1;
+    }
+    name
+}
+
+// A SUM over the data rows of `col`, written under them. `cents` is the precomputed
+// result, shown by viewers that do not recalculate formulas.
+pub fn write_sum(
+    sheet: &mut Worksheet,
+    formats: &Formats,
+    row: u32,
+    col: u16,
+    rows: usize,
+    cents: i64,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let name = column_name(col);
+    let formula = if rows == 0 {
+        "=0".to_string()
+    } else {
+        format!("=SUM({}2:{}{})", name, name, rows + 1)
+    };
+    handle_xlsx_error(sheet.write_formula_num(row, col, &formula, Some(&formats.total_money), cents as f64 / 100.0))
+}
+
+pub struct Properties<'a> {
+    pub title: &'a str,
+    pub author: &'a str,
+    pub generated_by: &'a str,
+    // Filters the export was run with, as "name = value".
+    pub filters: Vec<String>,
+}
+
+// The xlsxwriter bindings in use do not expose document properties, so the same
+// details go on a sheet of their own after the data.
+pub fn write_properties(
+    wb: &Workbook,
+    formats: &Formats,
+    properties: &Properties,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let mut sheet = handle_xlsx_error(wb.add_worksheet(Some("Properties")))?;
+    handle_xlsx_error(sheet.set_column(0, 0, 16.0, None))?;
+    handle_xlsx_error(sheet.set_column(1, 1, 40.0, None))?;
+
+    let generated_at = Local::now().format("%Y-%m-%d %H:%M:%S %:z").to_string();
+    let filters = if properties.filters.is_empty() {
+        "none".to_string()
+    } else {
+        properties.filters.join(", ")
+    };
+    let lines = [
+        ("Title", properties.title),
+        ("Author", properties.author),
+        ("Generated by", properties.generated_by),
+        ("Generated at", generated_at.as_str()),
+        ("Filters", filters.as_str()),
+    ];
+    for (i, (label, value)) in lines.iter().enumerate() {
+        handle_xlsx_error(sheet.write_string(i as u32, 0, label, Some(&formats.header)))?;
+        handle_xlsx_error(sheet.write_string(i as u32, 1, value, None))?;
+    }
+    Ok(())
+}